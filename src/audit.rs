@@ -0,0 +1,555 @@
+use crate::{
+    convert::public_key_size,
+    error::Error,
+    extract::{dearmor, is_armored, Source, Warning},
+    hash::{Algorithm, CipherAlgorithm, HashAlgorithm, Named, PgpHash, StringToKey, Usage},
+    protect::RawPacket,
+};
+use pgp::{
+    packet::{Packet, PacketParser},
+    types::{KeyId, KeyTrait},
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::{
+    fmt::Display,
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+};
+
+/// The rules of an audit: which protection parameters of secret keys are not acceptable.
+///
+/// A policy is read from a TOML file, in which every rule is optional and a missing rule is
+/// not checked. Algorithms are given by name (case-insensitive, e.g. `"sha1"` or `"3des"`) or
+/// by their OpenPGP ID:
+///
+/// ```toml
+/// forbid_unprotected = true
+/// forbidden_s2k = ["simple", "salted"]
+/// min_count = 1048576
+/// forbidden_digests = ["md5", "sha1"]
+/// forbidden_ciphers = ["cast5", "idea", "3des"]
+/// forbidden_usages = [255]
+/// min_rsa_bits = 3072
+/// ```
+///
+/// The default policy contains exactly these rules.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Policy {
+    /// Keys without a passphrase
+    pub forbid_unprotected: bool,
+    pub forbidden_s2k: Vec<StringToKey>,
+    /// The minimal number of bytes hashed by an iterated and salted string-to-key
+    pub min_count: Option<usize>,
+    /// The digests of the string-to-key function
+    pub forbidden_digests: Vec<HashAlgorithm>,
+    /// The ciphers that encrypt the secret key material
+    pub forbidden_ciphers: Vec<CipherAlgorithm>,
+    /// The string-to-key usages, e.g. 255 for a checksum that doesn't detect modifications
+    pub forbidden_usages: Vec<Usage>,
+    pub min_rsa_bits: Option<usize>,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            forbid_unprotected: true,
+            forbidden_s2k: vec![StringToKey::Simple, StringToKey::Salted],
+            min_count: Some(1048576),
+            forbidden_digests: vec![HashAlgorithm::MD5, HashAlgorithm::SHA1],
+            forbidden_ciphers: vec![
+                CipherAlgorithm::CAST5,
+                CipherAlgorithm::IDEA,
+                CipherAlgorithm::TripleDES,
+            ],
+            forbidden_usages: vec![Usage::TwoFiveFive],
+            min_rsa_bits: Some(3072),
+        }
+    }
+}
+
+/// The policy as written in the TOML file, before the algorithms are looked up
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    #[serde(default)]
+    forbid_unprotected: bool,
+    #[serde(default)]
+    forbidden_s2k: Vec<Id>,
+    min_count: Option<usize>,
+    #[serde(default)]
+    forbidden_digests: Vec<Id>,
+    #[serde(default)]
+    forbidden_ciphers: Vec<Id>,
+    #[serde(default)]
+    forbidden_usages: Vec<Id>,
+    min_rsa_bits: Option<usize>,
+}
+
+/// An algorithm or usage in the policy file
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Id {
+    Number(i32),
+    Name(String),
+}
+
+/// Look up algorithms by ID or by name, see [`Named::from_name`]
+fn lookup<T: Named>(field: &str, ids: Vec<Id>) -> Result<Vec<T>, Error> {
+    ids.into_iter()
+        .map(|id| {
+            let found = match &id {
+                Id::Number(n) => T::from_id(*n),
+                Id::Name(name) => T::from_name(name),
+            };
+            found.ok_or_else(|| {
+                let id = match id {
+                    Id::Number(n) => n.to_string(),
+                    Id::Name(name) => format!("\"{name}\""),
+                };
+                Error::InvalidPolicy(format!("unknown value {id} in {field}"))
+            })
+        })
+        .collect()
+}
+
+impl Policy {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, Error> {
+        let file: PolicyFile =
+            toml::from_str(toml).map_err(|e| Error::InvalidPolicy(e.message().to_string()))?;
+        Ok(Policy {
+            forbid_unprotected: file.forbid_unprotected,
+            forbidden_s2k: lookup("forbidden_s2k", file.forbidden_s2k)?,
+            min_count: file.min_count,
+            forbidden_digests: lookup("forbidden_digests", file.forbidden_digests)?,
+            forbidden_ciphers: lookup("forbidden_ciphers", file.forbidden_ciphers)?,
+            forbidden_usages: lookup("forbidden_usages", file.forbidden_usages)?,
+            min_rsa_bits: file.min_rsa_bits,
+        })
+    }
+
+    /// The findings for a protected key
+    pub fn check(&self, hash: &PgpHash) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let mut add = |rule, message| findings.push(Finding { rule, message });
+        if self.forbidden_s2k.contains(&hash.s2k) {
+            add(Rule::WeakS2k, format!("{:?} string-to-key", hash.s2k));
+        }
+        if let (Some(min), StringToKey::IteratedSalted, Some(count)) =
+            (self.min_count, hash.s2k, hash.count)
+        {
+            if count < min {
+                add(
+                    Rule::LowCount,
+                    format!("string-to-key count of {count} bytes, less than {min}"),
+                );
+            }
+        }
+        if self.forbidden_digests.contains(&hash.hash_algorithm) {
+            add(
+                Rule::WeakDigest,
+                format!("{:?} string-to-key digest", hash.hash_algorithm),
+            );
+        }
+        if self.forbidden_ciphers.contains(&hash.cipher_algorithm) {
+            add(
+                Rule::WeakCipher,
+                format!("{:?} cipher", hash.cipher_algorithm),
+            );
+        }
+        if self.forbidden_usages.contains(&hash.usage) {
+            add(
+                Rule::WeakUsage,
+                format!("string-to-key usage {}", hash.usage as i32),
+            );
+        }
+        if let Some(bits) = hash.bits {
+            findings.extend(self.check_public(hash.algorithm, bits));
+        }
+        findings
+    }
+
+    /// The findings for the public key, which apply to unprotected keys as well
+    fn check_public(&self, algorithm: Algorithm, bits: usize) -> Vec<Finding> {
+        let rsa = matches!(
+            algorithm,
+            Algorithm::RSAEncSign | Algorithm::RSAEncOnly | Algorithm::RsaSignOnly
+        );
+        match (rsa, self.min_rsa_bits) {
+            (true, Some(min)) if bits < min => vec![Finding {
+                rule: Rule::SmallRsaKey,
+                message: format!("RSA key of {bits} bits, less than {min}"),
+            }],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Audit all secret keys and subkeys of armored or binary OpenPGP data.
+    ///
+    /// Everything but secret key packets is skipped, and so are stubs without secret key
+    /// material. Keys that can't be checked and packets that can't be read are reported as
+    /// warnings; the rest of the data can't be read after a packet with an invalid header.
+    pub fn audit_bytes(&self, data: &[u8]) -> Result<Report, Error> {
+        let data = match is_armored(data) {
+            true => dearmor(data)?,
+            false => data.to_vec(),
+        };
+        let mut report = Report::default();
+        let mut rest = &data[..];
+        for index in 0.. {
+            if rest.is_empty() {
+                break;
+            }
+            let source = Source {
+                path: None,
+                packet: Some(index),
+            };
+            let packet = match RawPacket::split(&mut rest) {
+                Ok(packet) if packet.tag == 5 || packet.tag == 7 => packet,
+                Ok(_) => continue,
+                Err(error) => {
+                    report.warnings.push(Warning { source, error });
+                    break;
+                }
+            };
+            if let Some(stub) = packet.stub() {
+                let key_id = format!("key {}", hex::encode_upper(stub.key_id()));
+                report.skipped.push(Warning {
+                    source,
+                    error: Error::NoSecretKeyMaterial(key_id),
+                });
+                continue;
+            }
+            let packet = PacketParser::new(Cursor::new(packet.raw)).next();
+            let (key_id, fingerprint, is_subkey, (algorithm, bits), hash) = match packet {
+                Some(Ok(Packet::SecretKey(key))) => (
+                    key.key_id(),
+                    key.fingerprint(),
+                    false,
+                    public_key_size(key.public_params()),
+                    PgpHash::try_from(&key),
+                ),
+                Some(Ok(Packet::SecretSubkey(key))) => (
+                    key.key_id(),
+                    key.fingerprint(),
+                    true,
+                    public_key_size(key.public_params()),
+                    PgpHash::try_from(&key),
+                ),
+                Some(Ok(_)) | None => continue,
+                Some(Err(e)) => {
+                    report.warnings.push(Warning {
+                        source,
+                        error: Error::Packet(e),
+                    });
+                    continue;
+                }
+            };
+            let findings = match hash {
+                Ok(hash) => self.check(&hash),
+                Err(Error::Unprotected) => {
+                    let mut findings = match self.forbid_unprotected {
+                        true => vec![Finding {
+                            rule: Rule::Unprotected,
+                            message: "secret key is not protected by a passphrase".into(),
+                        }],
+                        false => Vec::new(),
+                    };
+                    findings.extend(self.check_public(algorithm, bits));
+                    findings
+                }
+                Err(error) => {
+                    report.warnings.push(Warning { source, error });
+                    continue;
+                }
+            };
+            report.keys.push(KeyAudit {
+                source,
+                key_id,
+                fingerprint,
+                is_subkey,
+                findings,
+            });
+        }
+        Ok(report)
+    }
+
+    /// Like [`Policy::audit_bytes`], with the path recorded in the [`Source`] of each key
+    pub fn audit_file(&self, path: impl AsRef<Path>) -> Result<Report, Error> {
+        let mut report = self.audit_bytes(&std::fs::read(path.as_ref())?)?;
+        report.path = Some(path.as_ref().to_path_buf());
+        let sources = report.keys.iter_mut().map(|k| &mut k.source);
+        let warnings = report.warnings.iter_mut().chain(&mut report.skipped);
+        for source in sources.chain(warnings.map(|w| &mut w.source)) {
+            source.path = Some(path.as_ref().to_path_buf());
+        }
+        Ok(report)
+    }
+}
+
+/// The rules of a [`Policy`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rule {
+    Unprotected,
+    WeakS2k,
+    LowCount,
+    WeakDigest,
+    WeakCipher,
+    WeakUsage,
+    SmallRsaKey,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 7] = [
+        Rule::Unprotected,
+        Rule::WeakS2k,
+        Rule::LowCount,
+        Rule::WeakDigest,
+        Rule::WeakCipher,
+        Rule::WeakUsage,
+        Rule::SmallRsaKey,
+    ];
+
+    /// A stable identifier, e.g. for reports
+    pub fn id(&self) -> &'static str {
+        match self {
+            Rule::Unprotected => "unprotected",
+            Rule::WeakS2k => "weak-s2k",
+            Rule::LowCount => "low-count",
+            Rule::WeakDigest => "weak-digest",
+            Rule::WeakCipher => "weak-cipher",
+            Rule::WeakUsage => "weak-usage",
+            Rule::SmallRsaKey => "small-rsa-key",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Rule::Unprotected => "The secret key is not protected by a passphrase",
+            Rule::WeakS2k => "The string-to-key function is not iterated",
+            Rule::LowCount => "The string-to-key function hashes too few bytes",
+            Rule::WeakDigest => "The string-to-key function uses a weak digest",
+            Rule::WeakCipher => "The secret key material is encrypted with a weak cipher",
+            Rule::WeakUsage => "The secret key material is protected by a weak checksum",
+            Rule::SmallRsaKey => "The RSA key is too small",
+        }
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+/// A violation of a rule
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub rule: Rule,
+    pub message: String,
+}
+
+/// The findings of a single secret key or subkey
+#[derive(Clone, Debug)]
+pub struct KeyAudit {
+    pub source: Source,
+    pub key_id: KeyId,
+    pub fingerprint: Vec<u8>,
+    pub is_subkey: bool,
+    /// Empty if the key complies with the policy
+    pub findings: Vec<Finding>,
+}
+
+/// The result of an audit of a single input
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The file that was audited, if the input was read from a file
+    pub path: Option<PathBuf>,
+    pub keys: Vec<KeyAudit>,
+    /// Keys and packets that could not be checked
+    pub warnings: Vec<Warning>,
+    /// Stubs without secret key material, which have nothing to check
+    pub skipped: Vec<Warning>,
+}
+
+impl Report {
+    /// The number of findings of all keys
+    pub fn violations(&self) -> usize {
+        self.keys.iter().map(|k| k.findings.len()).sum()
+    }
+
+    /// True if there was at least one secret key or stub and every packet could be checked.
+    /// Otherwise the data may hold keys that were not audited, e.g. because it is corrupt.
+    pub fn is_complete(&self) -> bool {
+        (!self.keys.is_empty() || !self.skipped.is_empty()) && self.warnings.is_empty()
+    }
+
+    /// The file, `-` if the data wasn't read from a file
+    fn file(&self) -> String {
+        self.path
+            .as_ref()
+            .map_or("-".into(), |p| p.display().to_string())
+    }
+}
+
+/// Write the findings as a SARIF 2.1.0 log with a single run.
+///
+/// Every finding is a result with the rule as `ruleId`, the file as its artifact location and
+/// the key as its logical location, whose name is the fingerprint. Keys that could not be
+/// checked are tool execution notifications, and so are stubs, as notes.
+pub fn write_sarif(reports: &[Report], writer: impl Write) -> Result<(), Error> {
+    let rules: Vec<_> = Rule::ALL
+        .iter()
+        .map(|rule| {
+            json!({
+                "id": rule.id(),
+                "shortDescription": { "text": rule.description() },
+                "defaultConfiguration": { "level": "error" },
+            })
+        })
+        .collect();
+    let mut results = Vec::new();
+    for (report, key) in reports
+        .iter()
+        .flat_map(|r| r.keys.iter().map(move |k| (r, k)))
+    {
+        let fingerprint = hex::encode_upper(&key.fingerprint);
+        for finding in &key.findings {
+            results.push(json!({
+                "ruleId": finding.rule.id(),
+                "ruleIndex": Rule::ALL.iter().position(|r| *r == finding.rule),
+                "level": "error",
+                "message": { "text": format!("{} (key {fingerprint})", finding.message) },
+                "locations": [{
+                    "physicalLocation": { "artifactLocation": { "uri": uri(&report.file()) } },
+                    "logicalLocations": [{
+                        "name": fingerprint,
+                        "kind": "resource",
+                    }],
+                }],
+                "partialFingerprints": { "keyFingerprint/v1": fingerprint },
+                "properties": {
+                    "keyId": hex::encode_upper(&key.key_id),
+                    "subkey": key.is_subkey,
+                },
+            }));
+        }
+    }
+    let notifications: Vec<_> = reports
+        .iter()
+        .flat_map(|r| &r.warnings)
+        .map(|w| json!({ "level": "warning", "message": { "text": w.to_string() } }))
+        .chain(
+            reports
+                .iter()
+                .flat_map(|r| &r.skipped)
+                .map(|w| json!({ "level": "note", "message": { "text": w.to_string() } })),
+        )
+        .collect();
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "pgp2hc",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "invocations": [{
+                "executionSuccessful": true,
+                "toolExecutionNotifications": notifications,
+            }],
+            "results": results,
+        }],
+    });
+    serde_json::to_writer_pretty(writer, &log).map_err(std::io::Error::from)?;
+    Ok(())
+}
+
+/// Write the findings as JUnit XML, with a test suite per file.
+///
+/// A key that complies with the policy is a passed test case named by its fingerprint. A key
+/// with findings has a failed test case per finding, named by its fingerprint and the rule,
+/// whose failure has the rule as its type.
+pub fn write_junit(reports: &[Report], mut writer: impl Write) -> Result<(), Error> {
+    let mut suites = String::new();
+    let (mut total, mut total_failures) = (0, 0);
+    for report in reports {
+        let file = report.file();
+        let mut cases = String::new();
+        let (mut tests, mut failures) = (0, 0);
+        for key in &report.keys {
+            let fingerprint = hex::encode_upper(&key.fingerprint);
+            if key.findings.is_empty() {
+                tests += 1;
+                cases += &format!(
+                    "    <testcase classname=\"{}\" name=\"{fingerprint}\"/>\n",
+                    xml(&file)
+                );
+            }
+            for finding in &key.findings {
+                tests += 1;
+                failures += 1;
+                cases += &format!(
+                    "    <testcase classname=\"{}\" name=\"{fingerprint} {}\">\n      \
+                     <failure type=\"{}\" message=\"{}\"/>\n    </testcase>\n",
+                    xml(&file),
+                    finding.rule,
+                    finding.rule,
+                    xml(&finding.message)
+                );
+            }
+        }
+        suites += &format!(
+            "  <testsuite name=\"{}\" tests=\"{tests}\" failures=\"{failures}\">\n{cases}  </testsuite>\n",
+            xml(&file)
+        );
+        total += tests;
+        total_failures += failures;
+    }
+    write!(
+        writer,
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <testsuites name=\"pgp2hc audit\" tests=\"{total}\" failures=\"{total_failures}\">\n\
+         {suites}</testsuites>\n"
+    )?;
+    Ok(())
+}
+
+/// A relative file path as relative URI reference, an absolute one as `file` URI. Backslashes
+/// separate directories like slashes, and everything but unreserved characters and `/` is
+/// percent-encoded, e.g. `C:\my keys\100%.asc` becomes `file:///C:/my%20keys/100%25.asc`.
+pub(crate) fn uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let (mut uri, path) = match path.as_bytes() {
+        [b'/', ..] => ("file://".to_string(), path.as_str()),
+        [drive, b':', b'/', ..] if drive.is_ascii_alphabetic() => {
+            (format!("file:///{}:", char::from(*drive)), &path[2..])
+        }
+        _ => (String::new(), path.as_str()),
+    };
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(char::from(b))
+            }
+            b => uri += &format!("%{b:02X}"),
+        }
+    }
+    uri
+}
+
+/// Escape text for XML attributes
+fn xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,229 @@
+use crate::{
+    cost::{Calibration, Cost, Measurement},
+    error::Error,
+    hash::{Algorithm, CipherAlgorithm, HashAlgorithm, Named, PgpHash, StringToKey, Usage},
+    s2k::reference_derivation,
+    verify::{decrypt, Verifier},
+};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The candidate passphrase of the measurements
+const PASSPHRASE: &[u8] = b"password";
+
+/// Measures the speed of the native string-to-key function and ciphers, configured with
+/// builder-style methods, to create a [`Calibration`].
+///
+/// The string-to-key function is measured for every combination of digest, cipher and count,
+/// on a single thread and on all threads. The speed of each digest's compression function is
+/// derived from the measurements at the highest count, where the string-to-key function does
+/// almost all of the work. Optionally, the reference implementation of the string-to-key
+/// function is measured as well, to show the speedup of the one that is used.
+#[derive(Clone, Debug)]
+pub struct Benchmark {
+    digests: Vec<HashAlgorithm>,
+    ciphers: Vec<CipherAlgorithm>,
+    counts: Vec<usize>,
+    threads: usize,
+    duration: Duration,
+    reference: bool,
+}
+
+impl Default for Benchmark {
+    /// All digests and ciphers at counts of 65536, 1048576 and 65011712 bytes, for 100 ms each
+    fn default() -> Self {
+        Benchmark {
+            digests: HashAlgorithm::all()
+                .into_iter()
+                .filter(|d| *d != HashAlgorithm::Unknown)
+                .collect(),
+            ciphers: CipherAlgorithm::all()
+                .into_iter()
+                .filter(|c| *c != CipherAlgorithm::Unknown)
+                .collect(),
+            counts: vec![65536, 1048576, 65011712],
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            duration: Duration::from_millis(100),
+            reference: false,
+        }
+    }
+}
+
+impl Benchmark {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn digests(mut self, digests: &[HashAlgorithm]) -> Self {
+        self.digests = digests.to_vec();
+        self
+    }
+
+    pub fn ciphers(mut self, ciphers: &[CipherAlgorithm]) -> Self {
+        self.ciphers = ciphers.to_vec();
+        self
+    }
+
+    /// The numbers of bytes hashed by the string-to-key function
+    pub fn counts(mut self, counts: &[usize]) -> Self {
+        self.counts = counts.to_vec();
+        self
+    }
+
+    /// The number of threads of the measurements on all cores (default: one per CPU core)
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// The minimal duration of each measurement. Each thread tests at least one candidate.
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Also measure the reference implementation of the string-to-key function on a single
+    /// thread, which hashes the repeated salt and passphrase piece by piece
+    pub fn reference(mut self, reference: bool) -> Self {
+        self.reference = reference;
+        self
+    }
+
+    /// Run all measurements, calling `progress` after each string-to-key measurement
+    pub fn run(&self, mut progress: impl FnMut(&Measurement)) -> Result<Calibration, Error> {
+        let mut calibration = Calibration {
+            threads: self.threads,
+            ..Default::default()
+        };
+        for &cipher in &self.ciphers {
+            let speed = self.cipher_speed(cipher)?;
+            calibration.ciphers.insert(cipher.name(), speed);
+        }
+
+        let max_count = self.counts.iter().copied().max().unwrap_or_default();
+        // compression function calls per second and the scaling, at the highest count
+        let mut digest_speeds: Vec<(HashAlgorithm, f64)> = Vec::new();
+        let mut scalings = Vec::new();
+        for &digest in &self.digests {
+            for &cipher in &self.ciphers {
+                for &count in &self.counts {
+                    let hash = sample_hash(digest, cipher, count);
+                    let single_core = self.measure(&hash, 1, false)?;
+                    let all_cores = self.measure(&hash, self.threads, false)?;
+                    let reference = match self.reference {
+                        true => Some(self.measure(&hash, 1, true)?),
+                        false => None,
+                    };
+                    let measurement = Measurement {
+                        digest: digest.name(),
+                        cipher: cipher.name(),
+                        count,
+                        single_core,
+                        all_cores,
+                        reference,
+                    };
+                    progress(&measurement);
+                    calibration.measurements.push(measurement);
+                    if count == max_count {
+                        // the time of the string-to-key function, without the decryption, so
+                        // that the calibration gives this speed back for the same cost
+                        let cost = Cost::of(&hash, PASSPHRASE.len())?;
+                        let seconds = 1.0 / single_core;
+                        let cipher_seconds =
+                            cost.cipher_blocks / calibration.ciphers[&cipher.name()];
+                        let s2k_seconds = match seconds - cipher_seconds {
+                            s if s > 0.0 => s,
+                            _ => seconds,
+                        };
+                        digest_speeds.push((digest, cost.s2k_blocks as f64 / s2k_seconds));
+                        scalings.push(all_cores / single_core);
+                    }
+                }
+            }
+        }
+        for &digest in &self.digests {
+            let speeds: Vec<_> = digest_speeds
+                .iter()
+                .filter(|(d, _)| *d == digest)
+                .map(|(_, s)| s)
+                .collect();
+            if !speeds.is_empty() {
+                let mean = speeds.iter().copied().sum::<f64>() / speeds.len() as f64;
+                calibration.digests.insert(digest.name(), mean);
+            }
+        }
+        calibration.scaling = match scalings.is_empty() {
+            true => 1.0,
+            false => scalings.iter().sum::<f64>() / scalings.len() as f64,
+        };
+        Ok(calibration)
+    }
+
+    /// Candidates tested per second against `hash` on `threads` threads, with the reference
+    /// implementation of the string-to-key function if `reference` is set
+    fn measure(&self, hash: &PgpHash, threads: usize, reference: bool) -> Result<f64, Error> {
+        let start = Instant::now();
+        let tested = thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut verifier = match reference {
+                            true => Verifier::with_derivation(hash, reference_derivation(hash)?),
+                            false => hash.verifier()?,
+                        };
+                        let mut tested = 0u64;
+                        while tested == 0 || start.elapsed() < self.duration {
+                            verifier.verify_bytes(PASSPHRASE)?;
+                            tested += 1;
+                        }
+                        Ok(tested)
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|w| w.join().unwrap())
+                .sum::<Result<u64, Error>>()
+        })?;
+        Ok(tested as f64 / start.elapsed().as_secs_f64())
+    }
+
+    /// Blocks decrypted per second with `cipher` on a single thread
+    fn cipher_speed(&self, cipher: CipherAlgorithm) -> Result<f64, Error> {
+        let key = vec![0; cipher.key_size().unwrap()];
+        let iv = vec![0; cipher.block_size().unwrap()];
+        let mut data = vec![0; 4096];
+        let start = Instant::now();
+        let mut rounds = 0u64;
+        while rounds == 0 || start.elapsed() < self.duration {
+            decrypt(cipher, &key, &iv, &mut data, false)?;
+            rounds += 1;
+        }
+        let blocks = (rounds * (data.len() / iv.len()) as u64) as f64;
+        Ok(blocks / start.elapsed().as_secs_f64())
+    }
+}
+
+/// A passphrase-encrypted message whose quick check fails for almost every candidate, so that
+/// only the string-to-key function and two cipher blocks are measured. It is too short for an
+/// MDC, which is never checked.
+pub(crate) fn sample_hash(digest: HashAlgorithm, cipher: CipherAlgorithm, count: usize) -> PgpHash {
+    let data = vec![0x5a; 2 * cipher.block_size().unwrap()];
+    PgpHash {
+        algorithm: Algorithm::Symmetric,
+        data_len: data.len(),
+        bits: None,
+        data,
+        s2k: StringToKey::IteratedSalted,
+        usage: Usage::Eighteen,
+        hash_algorithm: digest,
+        cipher_algorithm: cipher,
+        iv_len: None,
+        iv: None,
+        count: Some(count),
+        salt: Some(*b"pgp2hc\x00\x01"),
+        extra_data: None,
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{
+    format_entry, merge_duplicates, DearmorMode, Error, Extracted, Extractor, HashEntry,
+    HashFormat, Potfile,
+};
+use clap::{Parser, Subcommand};
+use std::{io, path::PathBuf};
+
+mod audit;
+mod benchmark;
+mod check;
+mod crack;
+mod decrypt;
+mod export;
+mod info;
+mod reprotect;
+mod show;
+mod wordlist;
+
+pub use audit::{audit, AuditArgs, ReportFormat};
+pub use benchmark::{benchmark, BenchmarkArgs};
+pub use check::{check, CheckArgs};
+pub use crack::{crack, CrackArgs};
+pub use decrypt::{decrypt, DecryptArgs};
+pub use export::{export, ExportArgs, PassphraseArgs};
+pub use info::{info, InfoArgs};
+pub use reprotect::{reprotect, ProtectionArgs, ReprotectArgs};
+pub use show::{show, ShowArgs};
+pub use wordlist::{wordlist, WordlistArgs};
+
+/// Extract hashcat/john hashes from encrypted secret keys in the OpenPGP format
+#[derive(Parser)]
+#[command(
+    version,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// The files containing the encrypted secret keys
+    #[clap(required = true)]
+    pub paths: Vec<PathBuf>,
+    /// The format in which to output the hash
+    #[clap(short, long, value_enum, default_value_t=HashFormat::Hashcat)]
+    pub format: HashFormat,
+    /// treat the given files as binary, even if they look ASCII-armored
+    #[clap(long)]
+    pub _no_dearmor: bool,
+    /// don't extract hashes from subkeys
+    #[clap(long)]
+    pub _no_subkeys: bool,
+    /// output keys found in several files (same fingerprint and protection) only once
+    #[clap(long)]
+    pub dedup: bool,
+    /// mark the hashes whose passphrase is in this john or hashcat potfile
+    #[clap(long)]
+    pub potfile: Option<PathBuf>,
+    /// leave out the hashes that are in the potfile
+    #[clap(long, requires = "potfile")]
+    pub left: bool,
+}
+
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)] // parsed once
+pub enum Command {
+    /// Recover passphrases with the built-in cracker
+    Crack(CrackArgs),
+    /// Build a personalised wordlist from the user IDs of keys and additional hints
+    Wordlist(WordlistArgs),
+    /// Show the cracked passphrases of keys and messages from a potfile
+    Show(ShowArgs),
+    /// Unlock keys with the recovered passphrase and export them with a new passphrase
+    Export(ExportArgs),
+    /// Decrypt a passphrase-encrypted message with the recovered passphrase
+    Decrypt(DecryptArgs),
+    /// Re-encrypt secret keys in place with a stronger string-to-key and cipher
+    Reprotect(ReprotectArgs),
+    /// Check the protection of secret keys against a policy, exits with 2 on violations
+    Audit(AuditArgs),
+    /// Explain the fields of hashes, which crackers support them, the work per candidate
+    /// passphrase and the expected speed
+    Info(InfoArgs),
+    /// Measure the speed of the string-to-key function on this computer and save a calibration
+    Benchmark(BenchmarkArgs),
+    /// Check the hashes in hash files for missing and invalid fields, exits with 2 if any line
+    /// is invalid
+    Check(CheckArgs),
+}
+
+pub fn extract_hash(args: &Cli) -> Result<String, Error> {
+    Ok(extract_hashes(args)?
+        .iter()
+        .map(|e| format_entry(e, &args.format))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Extract the hashes from all files given in `args`.
+/// If `args.dedup` is set, a key that appears in several files with identical protection
+/// parameters is returned once, with all files it was found in listed in `sources`.
+pub fn extract_hashes(args: &Cli) -> Result<Vec<HashEntry>, Error> {
+    let mut extractor =
+        Extractor::new()
+            .subkeys(!args._no_subkeys)
+            .dearmor(match args._no_dearmor {
+                true => DearmorMode::Never,
+                false => DearmorMode::Auto,
+            });
+    if let Some(path) = &args.potfile {
+        extractor = extractor.potfile(Potfile::open(path)?);
+    }
+    if args.left {
+        extractor = extractor.filter(|r| r.password.is_none());
+    }
+    let mut entries = Vec::new();
+
+    for path in &args.paths {
+        let items = extractor.extract_file(path).map_err(|e| match e {
+            Error::Io(e) => Error::Io(io::Error::new(e.kind(), format!("{}: {e}", path.display()))),
+            e => e,
+        })?;
+        for item in items {
+            let record = match item {
+                Extracted::Record(r) => r,
+                Extracted::Warning(w) => {
+                    eprintln!("warning: {w}");
+                    continue;
+                }
+            };
+            entries.push(HashEntry {
+                record,
+                sources: vec![path.clone()],
+            });
+        }
+    }
+
+    Ok(match args.dedup {
+        true => merge_duplicates(entries),
+        false => entries,
+    })
+}
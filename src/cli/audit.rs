@@ -0,0 +1,96 @@
+use crate::{
+    audit::{write_junit, write_sarif, Report},
+    Error, Policy,
+};
+use clap::{Args, ValueEnum};
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+#[derive(Args)]
+pub struct AuditArgs {
+    /// The files with the secret keys, armored or binary
+    #[clap(required = true)]
+    pub paths: Vec<PathBuf>,
+    /// The TOML file with the rules, instead of the built-in policy
+    #[clap(long)]
+    pub policy: Option<PathBuf>,
+    /// The format of the report
+    #[clap(short, long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+    /// Write the report to this file instead of stdout
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ReportFormat {
+    /// One line per finding: file, key fingerprint, rule and message, separated by tabs
+    Text,
+    /// SARIF 2.1.0, for code scanning and review tools
+    Sarif,
+    /// JUnit XML, for test dashboards
+    Junit,
+}
+
+/// Check every secret key and subkey of the files against the policy, and write a report of the
+/// findings. Returns the reports of the files.
+pub fn audit(args: &AuditArgs) -> Result<Vec<Report>, Error> {
+    let policy = match &args.policy {
+        Some(path) => Policy::open(path)?,
+        None => Policy::default(),
+    };
+    let mut reports = Vec::new();
+    for path in &args.paths {
+        let report = policy.audit_file(path)?;
+        for w in &report.warnings {
+            eprintln!("warning: {w}");
+        }
+        for w in &report.skipped {
+            eprintln!("skipping {w}");
+        }
+        if report.keys.is_empty() && report.skipped.is_empty() {
+            eprintln!("warning: {}: no secret keys found", path.display());
+        }
+        reports.push(report);
+    }
+
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout().lock()),
+    };
+    match args.format {
+        ReportFormat::Text => {
+            for (path, key) in reports
+                .iter()
+                .flat_map(|r| r.keys.iter().map(|k| (&r.path, k)))
+            {
+                for finding in &key.findings {
+                    writeln!(
+                        out,
+                        "{}\t{}\t{}\t{}",
+                        path.as_ref().unwrap().display(),
+                        hex::encode_upper(&key.fingerprint),
+                        finding.rule,
+                        finding.message
+                    )?;
+                }
+            }
+        }
+        ReportFormat::Sarif => write_sarif(&reports, &mut out)?,
+        ReportFormat::Junit => write_junit(&reports, &mut out)?,
+    }
+    out.flush()?;
+
+    let keys: usize = reports.iter().map(|r| r.keys.len()).sum();
+    let violations: usize = reports.iter().map(Report::violations).sum();
+    let failed = reports
+        .iter()
+        .flat_map(|r| &r.keys)
+        .filter(|k| !k.findings.is_empty())
+        .count();
+    eprintln!("{keys} keys audited, {failed} with {violations} violations");
+    Ok(reports)
+}
@@ -0,0 +1,97 @@
+use crate::{
+    benchmark::Benchmark,
+    hash::{CipherAlgorithm, HashAlgorithm, Named},
+    Calibration, Error,
+};
+use clap::Args;
+use std::{path::PathBuf, time::Duration};
+
+#[derive(Args)]
+pub struct BenchmarkArgs {
+    /// The digests to measure, e.g. `sha1,sha256`. All by default
+    #[clap(long, value_delimiter = ',', value_parser = parse_name::<HashAlgorithm>)]
+    pub digests: Vec<HashAlgorithm>,
+    /// The ciphers to measure, e.g. `cast5,aes256`. All by default
+    #[clap(long, value_delimiter = ',', value_parser = parse_name::<CipherAlgorithm>)]
+    pub ciphers: Vec<CipherAlgorithm>,
+    /// The numbers of bytes hashed by the string-to-key function
+    #[clap(long, value_delimiter = ',', default_value = "65536,1048576,65011712")]
+    pub counts: Vec<usize>,
+    /// Number of threads of the measurements on all cores, one per CPU core by default
+    #[clap(short = 'j', long)]
+    pub threads: Option<usize>,
+    /// Also measure the reference implementation of the string-to-key function on one thread,
+    /// and show the speedup of the one that is used
+    #[clap(long)]
+    pub reference: bool,
+    /// Milliseconds per measurement
+    #[clap(long, default_value_t = 100)]
+    pub time: u64,
+    /// Write the calibration to this file instead of `pgp2hc/calibration.toml` in the
+    /// configuration directory, where `info` and `crack` find it
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+fn parse_name<T: Named>(name: &str) -> Result<T, String> {
+    T::from_name(name)
+        .filter(|t| t.name() != "unknown")
+        .ok_or_else(|| format!("unknown algorithm {name}"))
+}
+
+/// Measure the speed of the string-to-key function for every digest, cipher and count on one
+/// and on all cores, print a table and save the calibration.
+/// Returns the calibration.
+pub fn benchmark(args: &BenchmarkArgs) -> Result<Calibration, Error> {
+    let path = match args.output.clone().or_else(Calibration::default_path) {
+        Some(path) => path,
+        None => {
+            return Err(Error::InvalidCalibration(
+                "no configuration directory, use --output".into(),
+            ))
+        }
+    };
+    let mut benchmark = Benchmark::new()
+        .counts(&args.counts)
+        .duration(Duration::from_millis(args.time))
+        .reference(args.reference);
+    if !args.digests.is_empty() {
+        benchmark = benchmark.digests(&args.digests);
+    }
+    if !args.ciphers.is_empty() {
+        benchmark = benchmark.ciphers(&args.ciphers);
+    }
+    if let Some(n) = args.threads {
+        benchmark = benchmark.threads(n);
+    }
+
+    let mut header = format!(
+        "{:<10} {:<12} {:>9} {:>14} {:>14}",
+        "digest", "cipher", "count", "1 thread", "all threads"
+    );
+    if args.reference {
+        header += &format!(" {:>14} {:>8}", "reference", "speedup");
+    }
+    println!("{header}");
+    let calibration = benchmark.run(|m| {
+        let mut row = format!(
+            "{:<10} {:<12} {:>9} {:>12.1}/s {:>12.1}/s",
+            m.digest, m.cipher, m.count, m.single_core, m.all_cores
+        );
+        if let Some(reference) = m.reference {
+            row += &format!(" {reference:>12.1}/s {:>7.2}x", m.single_core / reference);
+        }
+        println!("{row}");
+    })?;
+    for (digest, speed) in &calibration.digests {
+        println!("{digest}: {speed:.0} blocks/s per core");
+    }
+    calibration.save(&path)?;
+    eprintln!(
+        "calibration for {} threads (x{:.1}) saved to {}",
+        calibration.threads,
+        calibration.scaling,
+        path.display()
+    );
+    Ok(calibration)
+}
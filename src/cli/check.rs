@@ -0,0 +1,49 @@
+use crate::{check_hash, hash::PgpHash, Error, FieldError};
+use clap::Args;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct CheckArgs {
+    /// Files with hashes, one per line (hashcat or john format)
+    #[clap(required = true)]
+    pub paths: Vec<PathBuf>,
+}
+
+/// Check every line of the hash files and print the file, line number, field and reason of
+/// every invalid hash, like `keys.txt:3: field 4 (data): invalid hex string: Odd number of
+/// digits`. Blank lines are skipped. Returns the number of invalid lines.
+pub fn check(args: &CheckArgs) -> Result<usize, Error> {
+    let (mut valid, mut invalid) = (0, 0);
+    for path in &args.paths {
+        let bytes = std::fs::read(path)?;
+        for (i, line) in String::from_utf8_lossy(&bytes).lines().enumerate() {
+            match check_line(line) {
+                None => {}
+                Some(Ok(_)) => valid += 1,
+                Some(Err(e)) => {
+                    println!("{}:{}: {e}", path.display(), i + 1);
+                    invalid += 1;
+                }
+            }
+        }
+    }
+    eprintln!("{valid} valid and {invalid} invalid hashes");
+    Ok(invalid)
+}
+
+/// The hash on a line, None if the line is blank
+fn check_line(line: &str) -> Option<Result<PgpHash, FieldError>> {
+    if line.trim().is_empty() {
+        return None;
+    }
+    // john lines carry the user name before and GECOS fields after the hash
+    let Some(start) = line.find("$gpg$") else {
+        return Some(Err(FieldError {
+            index: 0,
+            name: "prefix",
+            reason: "no '$gpg$' hash on the line".into(),
+        }));
+    };
+    let hash = line[start..].split(':').next().unwrap_or_default().trim();
+    Some(check_hash(hash))
+}
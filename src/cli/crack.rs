@@ -0,0 +1,471 @@
+use crate::{
+    cost::Cost,
+    crack::{
+        apply_rules, load_rules, potfile_line, typo_variants, wordlist, Attack, Cracker,
+        KeyboardLayout, Mask, Positions, Rule, Session, Summary, WordlistFile,
+    },
+    hash::PgpHash,
+    parse_hash, Calibration, Encoding, Error, Extracted, Extractor, Hints, Potfile, UserInfo,
+};
+use clap::Args;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufReader, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+#[derive(Args)]
+pub struct CrackArgs {
+    /// Files with hashes (one per line, hashcat or john format) or encrypted keys and messages
+    #[clap(required = true)]
+    pub targets: Vec<PathBuf>,
+    /// The wordlist with one candidate per line, `-` for stdin
+    #[clap(short, long, required_unless_present_any = ["mask", "typo", "personal"])]
+    pub wordlist: Option<PathBuf>,
+    /// Files with hashcat-style rules to apply to every word, used one after the other
+    #[clap(short, long, requires = "wordlist", conflicts_with = "mask")]
+    pub rules: Vec<PathBuf>,
+    /// A hashcat-style mask, e.g. `Summer20?d?d?s`. Combined with a wordlist, the mask
+    /// candidates are appended to every word
+    #[clap(short, long)]
+    pub mask: Option<String>,
+    /// Prepend the mask candidates to the words instead of appending them
+    #[clap(long, requires_all = ["mask", "wordlist"])]
+    pub mask_prepend: bool,
+    /// Custom charset ?1 for the mask
+    #[clap(short = '1', long)]
+    pub custom_charset1: Option<String>,
+    /// Custom charset ?2 for the mask
+    #[clap(short = '2', long)]
+    pub custom_charset2: Option<String>,
+    /// Custom charset ?3 for the mask
+    #[clap(short = '3', long)]
+    pub custom_charset3: Option<String>,
+    /// Custom charset ?4 for the mask
+    #[clap(short = '4', long)]
+    pub custom_charset4: Option<String>,
+    /// Try the mask with increasing length, from --increment-min to --increment-max positions
+    #[clap(short, long, requires = "mask")]
+    pub increment: bool,
+    /// Start length of an incremental mask
+    #[clap(long, default_value_t = 1, requires = "increment")]
+    pub increment_min: usize,
+    /// End length of an incremental mask, the full mask by default
+    #[clap(long, requires = "increment")]
+    pub increment_max: Option<usize>,
+    /// A remembered passphrase that doesn't work anymore: try its typo variants instead
+    /// of a wordlist or mask, most likely first. Can be given several times
+    #[clap(long, conflicts_with_all = ["wordlist", "mask"])]
+    pub typo: Vec<String>,
+    /// Keyboard layout for adjacent-key slips: qwerty, qwertz, azerty, or a file with one row
+    /// of keys per line (unshifted, then shifted keys)
+    #[clap(long, default_value = "qwerty", requires = "typo")]
+    pub keyboard: String,
+    /// Number of typos to combine
+    #[clap(long, default_value_t = 1, requires = "typo")]
+    pub typo_depth: usize,
+    /// Try a personalised wordlist built from the user IDs and creation times of the targets,
+    /// like the `wordlist` subcommand
+    #[clap(long, conflicts_with_all = ["wordlist", "mask", "typo"])]
+    pub personal: bool,
+    /// An additional word for the personalised wordlist, e.g. the name of a pet or a place.
+    /// Can be given several times
+    #[clap(long, requires = "personal")]
+    pub hint: Vec<String>,
+    /// An additional year for the personalised wordlist, e.g. a year of birth
+    #[clap(long, requires = "personal")]
+    pub year: Vec<i32>,
+    /// Try every candidate in these encodings and Unicode normalisation forms, for keys
+    /// from old PGP versions or other systems
+    #[clap(long, value_enum, value_delimiter = ',', default_value = "utf8")]
+    pub encodings: Vec<Encoding>,
+    /// Seconds between the progress reports on stderr
+    #[clap(long, default_value_t = 10)]
+    pub status_timer: u64,
+    /// Skip the first N candidates of the attack
+    #[clap(short, long, default_value_t = 0, conflicts_with = "node")]
+    pub skip: u64,
+    /// Test at most N candidates, after the skipped ones
+    #[clap(short, long, conflicts_with = "node")]
+    pub limit: Option<u64>,
+    /// Test part N of M equal parts of the keyspace, e.g. `2/4`, to split the attack across
+    /// machines. The parts don't overlap
+    #[clap(long, value_parser = parse_node)]
+    pub node: Option<(u64, u64)>,
+    /// Append the cracked hashes to this john/hashcat potfile, and skip the hashes that are
+    /// already in it
+    #[clap(long)]
+    pub potfile: Option<PathBuf>,
+    /// Save the progress to this file. If it exists, the attack is resumed from it, so an
+    /// interrupted session is continued by running the same command again
+    #[clap(long)]
+    pub session: Option<PathBuf>,
+    /// Seconds between the checkpoints written to the session file
+    #[clap(long, default_value_t = 60, requires = "session")]
+    pub checkpoint_timer: u64,
+    /// Number of threads, one per CPU core by default
+    #[clap(short = 'j', long)]
+    pub threads: Option<usize>,
+    /// A calibration file with the speeds of this computer, to estimate the duration before
+    /// the attack starts. By default, the one saved by `benchmark` is used if there is one
+    #[clap(long)]
+    pub calibration: Option<PathBuf>,
+}
+
+/// Run a wordlist, mask, hybrid, typo or personalised attack and print every cracked hash as a `hash:password` potfile line
+pub fn crack(args: &CrackArgs) -> Result<Summary, Error> {
+    let session = match &args.session {
+        Some(path) => Some(Session::open(path, &session_attack(args))?),
+        None => None,
+    };
+    let resumed = session.as_ref().map_or(0, Session::position);
+    let mut cracker = Cracker::new().encodings(&args.encodings);
+    if let Some(n) = args.threads {
+        cracker = cracker.threads(n);
+    }
+    let potfile = match &args.potfile {
+        Some(path) if path.exists() => Potfile::open(path)?,
+        _ => Potfile::new(),
+    };
+    let mut hints = Hints::new();
+    let mut skipped = 0;
+    let mut in_potfile = 0;
+    for path in &args.targets {
+        for hash in load_targets(path, &mut hints)? {
+            if session.as_ref().is_some_and(|s| s.is_cracked(&hash)) {
+                skipped += 1;
+                continue;
+            }
+            if potfile.get(&hash).is_some() {
+                in_potfile += 1;
+                continue;
+            }
+            if let Err(e) = cracker.add(hash) {
+                eprintln!("warning: {}: skipping hash: {e}", path.display());
+            }
+        }
+    }
+    if let Some(session) = &session {
+        if resumed > 0 || skipped > 0 {
+            eprintln!(
+                "resuming session {} at candidate {resumed}, {skipped} hashes already cracked",
+                session.path().display()
+            );
+        }
+    }
+    if in_potfile > 0 {
+        eprintln!("{in_potfile} hashes are already in the potfile");
+    }
+    if cracker.hashes().is_empty() {
+        if skipped + in_potfile > 0 {
+            eprintln!("all hashes are already cracked");
+            return Ok(Summary::default());
+        }
+        return Err(Error::EmptyInput("no hashes to crack".into()));
+    }
+
+    let mut rules = Vec::new();
+    for path in &args.rules {
+        rules.extend(load_rules(BufReader::new(File::open(path)?))?);
+    }
+    let attack = match args.personal {
+        true => {
+            for word in &args.hint {
+                hints.add_word(word);
+            }
+            for year in &args.year {
+                hints.add_year(*year);
+            }
+            let candidates = hints.candidates();
+            Some(Attack::List(
+                candidates.into_iter().map(String::into_bytes).collect(),
+            ))
+        }
+        false => attack(args, &rules)?,
+    };
+    let keyspace = attack.as_ref().and_then(Attack::keyspace);
+    let range = slice(args, attack.as_ref())?;
+    // the number of candidates in the slice, if known
+    let size = keyspace
+        .map_or(range.end, |n| n.min(range.end))
+        .checked_sub(range.start)
+        .filter(|_| keyspace.is_some() || range.end != u64::MAX);
+    if range != (0..u64::MAX) {
+        match size {
+            Some(n) => eprintln!("testing candidates {} to {}", range.start, range.start + n),
+            None => eprintln!("testing candidates from {}", range.start),
+        }
+    }
+    let start = resumed.max(range.start);
+    let candidates: Positions = match &attack {
+        Some(attack) => attack.positions_from(start)?,
+        None => {
+            let words = Box::new(wordlist(BufReader::new(io::stdin())));
+            let candidates: Positions = match rules.is_empty() {
+                true => Box::new(words.map(|w| w.map(Some))),
+                false => apply_rules(words, &rules),
+            };
+            Box::new(candidates.skip(usize::try_from(start).unwrap_or(usize::MAX)))
+        }
+    };
+    let candidates =
+        candidates.take(usize::try_from(range.end.saturating_sub(start)).unwrap_or(usize::MAX));
+    if let Some(n) = size {
+        estimate(
+            args,
+            cracker.hashes(),
+            n.saturating_sub(start - range.start),
+        )?;
+    }
+    let total = cracker.hashes().len();
+    let mut cracker = cracker.status(Duration::from_secs(args.status_timer.max(1)), move |s| {
+        let tested = start - range.start + s.tested;
+        let progress = match size {
+            Some(n) => format!(
+                "{tested}/{n} ({:.2}%), ETA {}",
+                tested as f64 * 100.0 / n.max(1) as f64,
+                s.eta(n.saturating_sub(start - range.start))
+                    .map_or("-".into(), format_duration)
+            ),
+            None => tested.to_string(),
+        };
+        eprintln!(
+            "progress: {progress}, {:.1} candidates/s, {}/{total} cracked",
+            s.rate(),
+            s.cracked
+        );
+    });
+    let session = session.map(|s| Arc::new(Mutex::new(s)));
+    let stop = Arc::new(AtomicBool::new(false));
+    if let Some(session) = &session {
+        let saved = Arc::clone(session);
+        cracker = cracker.checkpoint(
+            Duration::from_secs(args.checkpoint_timer.max(1)),
+            move |position| {
+                let mut session = saved.lock().unwrap();
+                session.set_position(start + position);
+                if let Err(e) = session.save() {
+                    eprintln!("warning: could not save the session: {e}");
+                }
+            },
+        );
+        // stop at the next candidate, the final checkpoint is written when `run` returns
+        let flag = Arc::clone(&stop);
+        if let Err(e) = ctrlc::set_handler(move || {
+            // a second Ctrl-C quits without waiting
+            if flag.swap(true, Ordering::Relaxed) {
+                std::process::exit(130);
+            }
+            eprintln!("stopping, the session can be resumed with the same command");
+        }) {
+            eprintln!("warning: could not handle Ctrl-C: {e}");
+        }
+        cracker = cracker.stop_on(Arc::clone(&stop));
+    }
+    let mut potfile_out = match &args.potfile {
+        Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+        None => None,
+    };
+    let summary = cracker.run(candidates, |hash, password, encoding| {
+        let line = potfile_line(hash, password);
+        println!("{line}");
+        if let Some(file) = &mut potfile_out {
+            if let Err(e) = writeln!(file, "{line}") {
+                eprintln!("warning: could not write to the potfile: {e}");
+            }
+        }
+        if encoding != Encoding::Utf8 {
+            eprintln!("note: the passphrase above matched in the {encoding} encoding");
+        }
+        if let Some(session) = &session {
+            let mut session = session.lock().unwrap();
+            session.add_cracked(hash, password);
+            if let Err(e) = session.save() {
+                eprintln!("warning: could not save the session: {e}");
+            }
+        }
+    })?;
+    eprintln!(
+        "{}/{} hashes cracked, {} candidates tested",
+        summary.cracked,
+        cracker.hashes().len(),
+        summary.tested
+    );
+    Ok(summary)
+}
+
+/// Parse the `N/M` of `--node`
+fn parse_node(node: &str) -> Result<(u64, u64), String> {
+    let (n, m) = node.split_once('/').ok_or("expected N/M, e.g. 2/4")?;
+    let n: u64 = n.parse().map_err(|e| format!("invalid N: {e}"))?;
+    let m: u64 = m.parse().map_err(|e| format!("invalid M: {e}"))?;
+    match (1..=m).contains(&n) {
+        true => Ok((n, m)),
+        false => Err(format!("N must be between 1 and {m}")),
+    }
+}
+
+/// The candidate positions to test, given by `--node` or `--skip` and `--limit`
+fn slice(args: &CrackArgs, attack: Option<&Attack>) -> Result<Range<u64>, Error> {
+    match args.node {
+        Some((n, m)) => attack.and_then(|a| a.node_range(n, m)).ok_or_else(|| {
+            Error::InvalidSlice("--node needs a known keyspace, not a wordlist on stdin".into())
+        }),
+        None => Ok(args.skip..args.limit.map_or(u64::MAX, |l| args.skip.saturating_add(l))),
+    }
+}
+
+/// Everything that determines the candidates and their order, to make sure that a session is
+/// resumed with the same attack
+fn session_attack(args: &CrackArgs) -> String {
+    let charsets = [
+        &args.custom_charset1,
+        &args.custom_charset2,
+        &args.custom_charset3,
+        &args.custom_charset4,
+    ];
+    format!(
+        "{:?}",
+        (
+            &args.targets,
+            (&args.wordlist, &args.rules),
+            (&args.mask, args.mask_prepend, charsets),
+            (args.increment, args.increment_min, args.increment_max),
+            (&args.typo, &args.keyboard, args.typo_depth),
+            (args.personal, &args.hint, &args.year),
+            (args.skip, args.limit, args.node),
+        )
+    )
+}
+
+/// The attack given by the arguments, None for a wordlist on stdin
+fn attack(args: &CrackArgs, rules: &[Rule]) -> Result<Option<Attack>, Error> {
+    if !args.typo.is_empty() {
+        let layout = match KeyboardLayout::by_name(&args.keyboard) {
+            Some(layout) => layout,
+            None => KeyboardLayout::parse(&std::fs::read_to_string(&args.keyboard)?)?,
+        };
+        let remembered: Vec<_> = args.typo.iter().map(String::as_str).collect();
+        let variants = typo_variants(&remembered, &layout, args.typo_depth);
+        return Ok(Some(Attack::List(
+            variants.into_iter().map(String::into_bytes).collect(),
+        )));
+    }
+    let masks = match &args.mask {
+        Some(mask) => {
+            let custom: Vec<_> = [
+                &args.custom_charset1,
+                &args.custom_charset2,
+                &args.custom_charset3,
+                &args.custom_charset4,
+            ]
+            .into_iter()
+            .map(|c| c.as_deref().unwrap_or_default())
+            .collect();
+            let mask = Mask::parse(mask, &custom)?;
+            Some(match args.increment {
+                true => {
+                    mask.increments(args.increment_min, args.increment_max.unwrap_or(mask.len()))
+                }
+                false => vec![mask],
+            })
+        }
+        None => None,
+    };
+    let words = match &args.wordlist {
+        Some(path) if path == Path::new("-") && masks.is_some() => {
+            return Err(Error::InvalidArguments(
+                "--mask can't be combined with a wordlist on stdin".into(),
+            ))
+        }
+        Some(path) if path == Path::new("-") => return Ok(None),
+        Some(path) => Some(WordlistFile::open(path)?),
+        None => None,
+    };
+    Ok(Some(match (words, masks) {
+        (Some(words), None) if rules.is_empty() => Attack::Wordlist(words),
+        (Some(words), None) => Attack::WordlistRules(words, rules.to_vec()),
+        (None, Some(masks)) => Attack::Mask(masks),
+        (Some(words), Some(masks)) if args.mask_prepend => Attack::HybridMaskWordlist(masks, words),
+        (Some(words), Some(masks)) => Attack::HybridWordlistMask(words, masks),
+        (None, None) => unreachable!("clap requires a wordlist, a mask, a typo or --personal"),
+    }))
+}
+
+fn format_duration(d: Duration) -> String {
+    let s = d.as_secs();
+    format!("{:02}:{:02}:{:02}", s / 3600, s / 60 % 60, s % 60)
+}
+
+/// Print the expected duration of testing `candidates` against the hashes, if there is a
+/// calibration for their digests and ciphers
+fn estimate(args: &CrackArgs, hashes: &[PgpHash], candidates: u64) -> Result<(), Error> {
+    let Some(calibration) = Calibration::find(args.calibration.as_deref())? else {
+        return Ok(());
+    };
+    // most candidates have about this length, it only matters for low counts
+    let costs = hashes
+        .iter()
+        .map(|h| Cost::of(h, 8))
+        .collect::<Result<Vec<_>, _>>()?;
+    let threads = args
+        .threads
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+    if let Some(speed) = calibration.candidates_per_second(&costs, threads) {
+        let variants = candidates as f64 * args.encodings.len() as f64;
+        eprintln!(
+            "estimated duration: {} at {speed:.1} candidates/s",
+            format_duration(Duration::from_secs_f64(variants / speed))
+        );
+    }
+    Ok(())
+}
+
+/// Read the hashes from a hash file, or extract them if `path` is an OpenPGP file.
+/// The user IDs and key creation times are added to `hints`.
+pub(crate) fn load_targets(path: &Path, hints: &mut Hints) -> Result<Vec<PgpHash>, Error> {
+    let bytes = std::fs::read(path)?;
+    let text = match std::str::from_utf8(&bytes) {
+        Ok(text) if text.contains("$gpg$") => text,
+        _ => {
+            return Ok(Extractor::new()
+                .extract_bytes(&bytes)?
+                .filter_map(|item| match item {
+                    Extracted::Record(r) => {
+                        hints.add_record(&r);
+                        Some(r.hash)
+                    }
+                    Extracted::Warning(w) => {
+                        eprintln!("warning: {}: {w}", path.display());
+                        None
+                    }
+                })
+                .collect())
+        }
+    };
+
+    let mut hashes = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        // john lines carry the user name before and GECOS fields after the hash
+        let Some(start) = line.find("$gpg$") else {
+            continue;
+        };
+        let mut fields = line[start..].split(':');
+        let hash = fields.next().unwrap_or_default().trim();
+        match parse_hash(hash) {
+            Ok(hash) => hashes.push(hash),
+            Err(e) => eprintln!("warning: {}:{}: {e}", path.display(), i + 1),
+        }
+        // <username>:<hash>:::<name_with_email>::<filename>
+        if let Some(user) = fields.nth(2).filter(|u| !u.is_empty()) {
+            hints.add_user(&UserInfo::from(user));
+        }
+    }
+    Ok(hashes)
+}
@@ -0,0 +1,109 @@
+use super::export::{create_private, PassphraseArgs};
+use crate::{
+    decrypt::{decrypt_message, Literal},
+    Error, Extracted, Extractor,
+};
+use clap::Args;
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+#[derive(Args)]
+pub struct DecryptArgs {
+    /// The passphrase-encrypted message (e.g. from `gpg -c` or a PGP Zip archive)
+    pub path: PathBuf,
+    #[command(flatten)]
+    pub passphrase: PassphraseArgs,
+    /// Write the plaintext to this file (`-` for stdout) instead of its original file name
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+    /// The directory for the files with their original names
+    #[clap(short, long, default_value = ".", conflicts_with = "output")]
+    pub directory: PathBuf,
+    /// Overwrite existing files
+    #[clap(short, long)]
+    pub force: bool,
+}
+
+/// Decrypt the message and write its files, with the file names of the literal data packets.
+/// Returns the number of files.
+pub fn decrypt(args: &DecryptArgs) -> Result<usize, Error> {
+    let data = fs::read(&args.path)?;
+    let passphrase = args.passphrase.get("the message", |potfile| {
+        Ok(Extractor::new()
+            .potfile(potfile.clone())
+            .extract_bytes(&data)?
+            .find_map(|item| match item {
+                Extracted::Record(r) => r.password,
+                Extracted::Warning(_) => None,
+            }))
+    })?;
+
+    let message = decrypt_message(&data, &passphrase)?;
+    if !message.integrity_protected {
+        eprintln!(
+            "warning: {}: the message has no modification detection code, it may have been modified",
+            args.path.display()
+        );
+    }
+    match &args.output {
+        Some(path) if path.as_os_str() == "-" => {
+            let mut out = io::stdout().lock();
+            for literal in &message.literals {
+                out.write_all(&literal.data)?;
+            }
+        }
+        Some(path) => {
+            let data: Vec<u8> = message
+                .literals
+                .iter()
+                .flat_map(|l| l.data.clone())
+                .collect();
+            write_file(path, &data, args.force)?;
+        }
+        None => {
+            for literal in &message.literals {
+                let path = args.directory.join(output_name(literal, &args.path));
+                write_file(&path, &literal.data, args.force)?;
+                eprintln!("{}: {} bytes", path.display(), literal.data.len());
+            }
+        }
+    }
+    Ok(message.literals.len())
+}
+
+/// The original file name without directories, or the name of the encrypted file without its
+/// extension if the message doesn't have one
+fn output_name(literal: &Literal, input: &Path) -> PathBuf {
+    let name = String::from_utf8_lossy(&literal.file_name);
+    match name.rsplit(['/', '\\']).next().unwrap_or_default() {
+        "" | "." | ".." | "_CONSOLE" => {
+            let stem = input.file_stem().unwrap_or_default();
+            match stem == input.file_name().unwrap_or_default() {
+                true => Path::new(stem).with_extension("out"),
+                false => PathBuf::from(stem),
+            }
+        }
+        name => PathBuf::from(name),
+    }
+}
+
+/// Write decrypted data to a file that only its owner can read
+pub(super) fn write_file(path: &Path, data: &[u8], force: bool) -> Result<(), Error> {
+    let mut file = create_private(path, force)?;
+    file.write_all(data)?;
+    Ok(())
+}
+
+/// Point to `--force` if the file couldn't be created because it exists
+pub(super) fn exists_error(e: io::Error, path: &Path) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::AlreadyExists => io::Error::new(
+            e.kind(),
+            format!("{} exists, use --force to overwrite it", path.display()),
+        ),
+        _ => e,
+    }
+}
@@ -0,0 +1,143 @@
+use super::{decrypt::exists_error, reprotect::ProtectionArgs};
+use crate::{
+    hash::PgpHash,
+    protect::{read_secret_keys, reprotect},
+    Error, Potfile,
+};
+use clap::Args;
+use pgp::{ser::Serialize, types::KeyTrait};
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// The file with the encrypted secret keys, armored or binary
+    pub path: PathBuf,
+    #[command(flatten)]
+    pub passphrase: PassphraseArgs,
+    /// The new passphrase
+    #[clap(
+        short,
+        long,
+        required_unless_present = "new_passphrase_file",
+        conflicts_with = "new_passphrase_file"
+    )]
+    pub new_passphrase: Option<String>,
+    /// Read the new passphrase from the first line of this file
+    #[clap(long)]
+    pub new_passphrase_file: Option<PathBuf>,
+    #[command(flatten)]
+    pub protection: ProtectionArgs,
+    /// Write ASCII-armored output
+    #[clap(short, long)]
+    pub armor: bool,
+    /// Write the keys to this file instead of stdout
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+    /// Overwrite an existing output file
+    #[clap(short, long, requires = "output")]
+    pub force: bool,
+}
+
+/// The recovered passphrase of a key or message
+#[derive(Args)]
+pub struct PassphraseArgs {
+    /// The recovered passphrase
+    #[clap(
+        short,
+        long,
+        required_unless_present_any = ["passphrase_file", "potfile"],
+        conflicts_with_all = ["passphrase_file", "potfile"]
+    )]
+    pub passphrase: Option<String>,
+    /// Read the recovered passphrase from the first line of this file
+    #[clap(long, conflicts_with = "potfile")]
+    pub passphrase_file: Option<PathBuf>,
+    /// Take the recovered passphrase from this john or hashcat potfile
+    #[clap(long)]
+    pub potfile: Option<PathBuf>,
+}
+
+impl PassphraseArgs {
+    /// The passphrase, looked up with `lookup` if it is taken from the potfile.
+    /// `what` names the key or message in the error if it is not there.
+    pub(super) fn get(
+        &self,
+        what: &str,
+        lookup: impl FnOnce(&Potfile) -> Result<Option<Vec<u8>>, Error>,
+    ) -> Result<Vec<u8>, Error> {
+        match (&self.passphrase, &self.passphrase_file, &self.potfile) {
+            (Some(p), _, _) => Ok(p.as_bytes().to_vec()),
+            (None, Some(path), _) => Ok(read_passphrase(path)?.into_bytes()),
+            (None, None, Some(path)) => lookup(&Potfile::open(path)?)?
+                .ok_or_else(|| Error::MissingPassphrase(format!("{what} in the potfile"))),
+            (None, None, None) => unreachable!("required by clap"),
+        }
+    }
+}
+
+/// Unlock the keys with the recovered passphrase and export them protected with the new
+/// passphrase, by default with AES-256 and an iterated SHA-256 string-to-key.
+/// Returns the number of keys.
+pub fn export(args: &ExportArgs) -> Result<usize, Error> {
+    let keys = read_secret_keys(&fs::read(&args.path)?)?;
+    let protection = args.protection.protection()?;
+    let new_passphrase = match (&args.new_passphrase, &args.new_passphrase_file) {
+        (Some(p), _) => p.clone(),
+        (None, Some(path)) => read_passphrase(path)?,
+        (None, None) => unreachable!("required by clap"),
+    };
+
+    let mut out = Vec::new();
+    for key in &keys {
+        let key_id = format!("key {}", hex::encode_upper(key.key_id()));
+        let passphrase = args.passphrase.get(&key_id, |potfile| {
+            Ok(potfile.get(&PgpHash::try_from(key)?).map(<[u8]>::to_vec))
+        })?;
+        let key = reprotect(key, &utf8(passphrase)?, &new_passphrase, &protection)?;
+        match args.armor {
+            true => out.extend(key.to_armored_bytes(None)?),
+            false => key.to_writer(&mut out)?,
+        }
+    }
+
+    match &args.output {
+        Some(path) => create_private(path, args.force)?.write_all(&out)?,
+        None => io::stdout().lock().write_all(&out)?,
+    }
+    Ok(keys.len())
+}
+
+/// Create a file for secret keys or plaintext, which only its owner can read and write. An existing file is
+/// only overwritten with `force`, and then loses the permissions it had.
+pub(super) fn create_private(path: &Path, force: bool) -> Result<File, Error> {
+    let mut options = OpenOptions::new();
+    options
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .create_new(!force);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let file = options.open(path).map_err(|e| exists_error(e, path))?;
+    // the mode only applies to new files
+    #[cfg(unix)]
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    Ok(file)
+}
+
+/// The first line of a file, without the line break
+pub(super) fn read_passphrase(path: &Path) -> Result<String, Error> {
+    let content = fs::read_to_string(path)?;
+    Ok(content.lines().next().unwrap_or_default().to_string())
+}
+
+/// The `pgp` crate only takes passphrases as strings
+pub(super) fn utf8(passphrase: Vec<u8>) -> Result<String, Error> {
+    String::from_utf8(passphrase).map_err(|_| Error::InvalidPassphrase("not valid UTF-8".into()))
+}
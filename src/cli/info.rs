@@ -0,0 +1,109 @@
+use super::crack::load_targets;
+use crate::{
+    cost::Cost,
+    describe::{crackers, describe},
+    hash::PgpHash,
+    parse_hash, Calibration, Error, Hints,
+};
+use clap::Args;
+use std::path::{Path, PathBuf};
+
+#[derive(Args)]
+pub struct InfoArgs {
+    /// `$gpg$` hashes, files with hashes or files with encrypted keys and messages
+    #[clap(required = true)]
+    pub hashes: Vec<String>,
+    /// The length of the candidate passphrases in bytes, which matters for low counts and the
+    /// description of the count
+    #[clap(long, default_value_t = 8)]
+    pub length: usize,
+    /// A calibration file with the speeds of this computer, to estimate the guesses per second.
+    /// By default, the one saved by `benchmark` is used if there is one
+    #[clap(long)]
+    pub calibration: Option<PathBuf>,
+}
+
+/// Print every field of every hash with its meaning, the crackers that support the hash, the
+/// work per candidate passphrase and, with a calibration, the expected guesses per second.
+/// Returns the number of hashes.
+pub fn info(args: &InfoArgs) -> Result<usize, Error> {
+    let calibration = Calibration::find(args.calibration.as_deref())?;
+    let hashes = load_hashes(&args.hashes)?;
+    for hash in &hashes {
+        let line = hash.to_string();
+        match line.char_indices().nth(60) {
+            Some((i, _)) => println!("{}...", &line[..i]),
+            None => println!("{line}"),
+        }
+        for field in describe(hash, args.length) {
+            let index = field.index.map(|i| i.to_string()).unwrap_or_default();
+            println!(
+                "  {index:>2} {:<16} {:<35} {}",
+                field.name, field.value, field.meaning
+            );
+        }
+        let crackers: Vec<_> = crackers(hash).iter().map(|c| c.to_string()).collect();
+        match crackers.is_empty() {
+            true => println!("  crackers: none"),
+            false => println!("  crackers: {}", crackers.join(", ")),
+        }
+        let cost = match Cost::of(hash, args.length) {
+            Ok(cost) => cost,
+            Err(e) => {
+                println!("  cost: {e}");
+                continue;
+            }
+        };
+        for line in cost_lines(&cost, calibration.as_ref()) {
+            println!("  {line}");
+        }
+    }
+    Ok(hashes.len())
+}
+
+/// Parse the hashes given on the command line, or read them from files
+pub(super) fn load_hashes(args: &[String]) -> Result<Vec<PgpHash>, Error> {
+    let mut hashes = Vec::new();
+    for arg in args {
+        match arg.trim().starts_with("$gpg$") {
+            true => hashes.push(parse_hash(arg.trim())?),
+            false => hashes.extend(load_targets(Path::new(arg), &mut Hints::new())?),
+        }
+    }
+    Ok(hashes)
+}
+
+/// The work per candidate and the speed, if it is known
+fn cost_lines(cost: &Cost, calibration: Option<&Calibration>) -> Vec<String> {
+    let mut check = String::new();
+    if cost.check_blocks >= 1.0 {
+        check = format!(", {} SHA1 blocks to check it", cost.check_blocks);
+    } else if cost.check_blocks > 0.0 {
+        check = format!(
+            ", {:.5} SHA1 blocks on average to check it",
+            cost.check_blocks
+        );
+    }
+    let cipher = match cost.cipher_blocks.fract() {
+        0.0 => format!("{} {:?} blocks", cost.cipher_blocks, cost.cipher),
+        _ => format!(
+            "{:.5} {:?} blocks on average",
+            cost.cipher_blocks, cost.cipher
+        ),
+    };
+    let mut lines = vec![format!(
+        "work per candidate: {} {:?} blocks ({} x {} bytes), {cipher}{check}",
+        cost.s2k_blocks, cost.digest, cost.contexts, cost.hashed_bytes,
+    )];
+    if let Some(c) = calibration {
+        lines.push(match c.guesses_per_second(cost) {
+            Some(speed) => format!(
+                "speed: {speed:.1} guesses/s on one core, {:.1} on {} threads",
+                speed * c.scaling,
+                c.threads
+            ),
+            None => "speed: unknown, the digest or cipher is not in the calibration".into(),
+        });
+    }
+    lines
+}
@@ -0,0 +1,206 @@
+use super::export::{create_private, utf8, PassphraseArgs};
+use crate::{
+    extract::{dearmor, is_armored},
+    protect::{coded_count, reprotect_data, Protection},
+    Error, Extracted, Extractor,
+};
+use clap::{Args, ValueEnum};
+use pgp::{
+    armor::{self, BlockType},
+    crypto::{hash::HashAlgorithm, sym::SymmetricKeyAlgorithm},
+};
+use std::{
+    ffi::OsString,
+    fs::{self, Permissions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+#[derive(Args)]
+pub struct ReprotectArgs {
+    /// The files with the secret keys, armored or binary. They are replaced, the originals are
+    /// kept with `.bak` appended to their names. Existing backups are only overwritten with
+    /// `--force`
+    #[clap(required = true)]
+    pub paths: Vec<PathBuf>,
+    #[command(flatten)]
+    pub passphrase: PassphraseArgs,
+    #[command(flatten)]
+    pub protection: ProtectionArgs,
+    /// Write the keys to this file instead of replacing the original
+    #[clap(short, long, conflicts_with = "no_backup")]
+    pub output: Option<PathBuf>,
+    /// Don't keep the originals
+    #[clap(long)]
+    pub no_backup: bool,
+    /// Overwrite existing backups
+    #[clap(short, long, conflicts_with_all = ["output", "no_backup"])]
+    pub force: bool,
+}
+
+/// The protection of re-encrypted keys
+#[derive(Args)]
+pub struct ProtectionArgs {
+    /// The digest of the string-to-key function
+    #[clap(long, value_enum, default_value_t = S2kDigest::Sha256)]
+    pub s2k_digest_algo: S2kDigest,
+    /// The cipher that encrypts the secret key material
+    #[clap(long, value_enum, default_value_t = S2kCipher::Aes256)]
+    pub s2k_cipher_algo: S2kCipher,
+    /// The number of bytes the string-to-key function hashes, rounded up to the next count the
+    /// format can represent (at most 65011712)
+    #[clap(long, default_value_t = 65011712)]
+    pub s2k_count: usize,
+    /// Use Argon2 instead of an iterated and salted hash (RFC 9580)
+    #[clap(long)]
+    pub argon2: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum S2kDigest {
+    Sha256,
+    Sha512,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum S2kCipher {
+    Aes128,
+    Aes192,
+    Aes256,
+}
+
+impl ProtectionArgs {
+    pub(super) fn protection(&self) -> Result<Protection, Error> {
+        if self.argon2 {
+            // RFC 9580 only allows Argon2 together with AEAD protection (usage 253)
+            return Err(Error::UnsupportedS2k(
+                "Argon2 requires AEAD-protected keys (RFC 9580), which can't be written yet".into(),
+            ));
+        }
+        Ok(Protection {
+            cipher: match self.s2k_cipher_algo {
+                S2kCipher::Aes128 => SymmetricKeyAlgorithm::AES128,
+                S2kCipher::Aes192 => SymmetricKeyAlgorithm::AES192,
+                S2kCipher::Aes256 => SymmetricKeyAlgorithm::AES256,
+            },
+            hash: match self.s2k_digest_algo {
+                S2kDigest::Sha256 => HashAlgorithm::SHA2_256,
+                S2kDigest::Sha512 => HashAlgorithm::SHA2_512,
+            },
+            count: coded_count(self.s2k_count),
+        })
+    }
+}
+
+/// Re-encrypt all secret keys and subkeys of the files with the given protection and the same
+/// passphrase. Everything but the secret key material stays byte-identical.
+/// Returns the number of re-protected keys.
+pub fn reprotect(args: &ReprotectArgs) -> Result<usize, Error> {
+    if args.output.is_some() && args.paths.len() > 1 {
+        return Err(Error::InvalidArguments(
+            "--output can only be used with a single file".into(),
+        ));
+    }
+    let protection = args.protection.protection()?;
+    // refuse before any file is replaced
+    if args.output.is_none() && !args.no_backup && !args.force {
+        if let Some(backup) = args
+            .paths
+            .iter()
+            .map(|p| backup_path(p))
+            .find(|b| fs::exists(b).unwrap_or(true))
+        {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists, use --force to overwrite it", backup.display()),
+            )));
+        }
+    }
+    let mut total = 0;
+    for path in &args.paths {
+        let input = fs::read(path)?;
+        let permissions = fs::metadata(path)?.permissions();
+        let passphrase = args
+            .passphrase
+            .get(&path.display().to_string(), |potfile| {
+                Ok(Extractor::new()
+                    .potfile(potfile.clone())
+                    .extract_bytes(&input)?
+                    .find_map(|item| match item {
+                        Extracted::Record(r) => r.password,
+                        Extracted::Warning(_) => None,
+                    }))
+            })?;
+        let passphrase = utf8(passphrase)?;
+
+        let armored = is_armored(&input);
+        let data = match armored {
+            true => dearmor(&input)?,
+            false => input.clone(),
+        };
+        let (data, count) = reprotect_data(&data, &passphrase, &passphrase, &protection)?;
+        let output = match armored {
+            true => {
+                let mut output = Vec::new();
+                armor::write(&Raw(&data), BlockType::PrivateKey, &mut output, None)?;
+                output
+            }
+            false => data,
+        };
+
+        match &args.output {
+            Some(out) => write_keys(out, &output, &permissions, true)?,
+            None => {
+                if !args.no_backup {
+                    write_keys(&backup_path(path), &input, &permissions, args.force)?;
+                }
+                // replace the file at once, a partly written key file would be lost
+                let mut tmp = path.clone().into_os_string();
+                tmp.push(".tmp");
+                write_keys(Path::new(&tmp), &output, &permissions, true)?;
+                fs::rename(&tmp, path)?;
+            }
+        }
+        eprintln!(
+            "{}: re-protected {count} keys ({:?}, {:?}, {} bytes)",
+            path.display(),
+            protection.cipher,
+            protection.hash,
+            protection.count_bytes()
+        );
+        total += count;
+    }
+    Ok(total)
+}
+
+/// Raw OpenPGP data, to be armored
+struct Raw<'a>(&'a [u8]);
+
+impl pgp::ser::Serialize for Raw<'_> {
+    fn to_writer<W: std::io::Write>(&self, writer: &mut W) -> pgp::errors::Result<()> {
+        writer.write_all(self.0)?;
+        Ok(())
+    }
+}
+
+/// Write keys to `path` with the `permissions` of the original file. The file is created
+/// readable only by its owner, so the keys are never exposed by the umask. An existing file is
+/// only overwritten with `force`.
+fn write_keys(
+    path: &Path,
+    data: &[u8],
+    permissions: &Permissions,
+    force: bool,
+) -> Result<(), Error> {
+    let mut file = create_private(path, force)?;
+    file.set_permissions(permissions.clone())?;
+    file.write_all(data)?;
+    Ok(())
+}
+
+/// The path of the original of a re-protected file, `<file>.bak`
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = OsString::from(path);
+    backup.push(".bak");
+    backup.into()
+}
@@ -0,0 +1,54 @@
+use crate::{extract::Record, potfile::encode_password, Error, Extracted, Extractor, Potfile};
+use clap::Args;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct ShowArgs {
+    /// The files with the encrypted keys and messages
+    #[clap(required = true)]
+    pub paths: Vec<PathBuf>,
+    /// The john or hashcat potfile with the cracked hashes
+    #[clap(short, long)]
+    pub potfile: PathBuf,
+}
+
+/// Print the file, key ID, user ID and passphrase of every cracked key or message, one per line
+/// and separated by tabs. Returns the number of cracked hashes.
+pub fn show(args: &ShowArgs) -> Result<usize, Error> {
+    let extractor = Extractor::new()
+        .potfile(Potfile::open(&args.potfile)?)
+        .filter(|r| r.password.is_some());
+    let mut cracked = 0;
+    for path in &args.paths {
+        for item in extractor.extract_file(path)? {
+            match item {
+                Extracted::Record(r) => {
+                    println!("{}", show_line(&r));
+                    cracked += 1;
+                }
+                Extracted::Warning(w) => eprintln!("warning: {w}"),
+            }
+        }
+    }
+    Ok(cracked)
+}
+
+/// The file, key ID, user ID and passphrase, separated by tabs, with `-` for what a message doesn't have
+fn show_line(record: &Record) -> String {
+    let path = record
+        .source
+        .path
+        .as_ref()
+        .map_or("-".into(), |p| p.display().to_string());
+    let key_id = record.key_id.as_ref().map_or("-".into(), hex::encode_upper);
+    let user = record.user.as_ref().map_or("-".into(), |u| {
+        [&u.name, &u.comment, &u.email]
+            .into_iter()
+            .filter(|s| !s.is_empty())
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(" ")
+    });
+    let password = record.password.as_deref().map(encode_password);
+    format!("{path}\t{key_id}\t{user}\t{}", password.unwrap_or_default())
+}
@@ -0,0 +1,57 @@
+use super::crack::load_targets;
+use crate::{Error, Hints};
+use clap::Args;
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+#[derive(Args)]
+pub struct WordlistArgs {
+    /// Encrypted keys or hash files in the john format, whose user IDs and key creation times
+    /// are used
+    #[clap(required_unless_present = "hint")]
+    pub paths: Vec<PathBuf>,
+    /// An additional word, e.g. the name of a pet, a partner or a place.
+    /// Can be given several times
+    #[clap(long)]
+    pub hint: Vec<String>,
+    /// An additional year, e.g. a year of birth. Can be given several times
+    #[clap(long)]
+    pub year: Vec<i32>,
+    /// Write the wordlist to this file instead of stdout
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+/// Write a personalised wordlist built from the user IDs of the keys and the given hints,
+/// most likely candidates first. Returns the number of candidates.
+pub fn wordlist(args: &WordlistArgs) -> Result<usize, Error> {
+    let mut hints = Hints::new();
+    for path in &args.paths {
+        load_targets(path, &mut hints)?;
+    }
+    for word in &args.hint {
+        hints.add_word(word);
+    }
+    for year in &args.year {
+        hints.add_year(*year);
+    }
+    if hints.is_empty() {
+        return Err(Error::EmptyInput(
+            "no user IDs found, give some words with --hint".into(),
+        ));
+    }
+
+    let candidates = hints.candidates();
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    for candidate in &candidates {
+        writeln!(out, "{candidate}")?;
+    }
+    out.flush()?;
+    Ok(candidates.len())
+}
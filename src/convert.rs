@@ -1,9 +1,91 @@
+use crate::error::Error;
 use crate::hash::{Algorithm, CipherAlgorithm, HashAlgorithm, PgpHash, StringToKey, Usage};
 use pgp::{
-    packet::{SecretKey, SecretSubkey},
+    packet::{SecretKey, SecretSubkey, SymKeyEncryptedSessionKey},
     types::{PublicParams, SecretParams},
+    Edata, SignedSecretKey, SignedSecretSubKey,
 };
-use std::error::Error;
+
+/// Symmetrically encrypted data longer than this is truncated in the hash.
+/// The first bytes are enough to verify a passphrase, but the MDC at the end is lost.
+pub(crate) const MAX_SYMMETRIC_DATA_LEN: usize = 8192;
+
+impl TryFrom<&SecretKey> for PgpHash {
+    type Error = Error;
+
+    fn try_from(key: &SecretKey) -> Result<Self, Self::Error> {
+        helper(key.public_params(), key.secret_params())
+    }
+}
+
+impl TryFrom<&SecretSubkey> for PgpHash {
+    type Error = Error;
+
+    fn try_from(key: &SecretSubkey) -> Result<Self, Self::Error> {
+        helper(key.public_params(), key.secret_params())
+    }
+}
+
+/// The hash of the primary key. Use [`crate::Extractor::extract_key`] to get all keys
+/// together with their user ID.
+impl TryFrom<&SignedSecretKey> for PgpHash {
+    type Error = Error;
+
+    fn try_from(key: &SignedSecretKey) -> Result<Self, Self::Error> {
+        PgpHash::try_from(&key.primary_key)
+    }
+}
+
+impl TryFrom<&SignedSecretSubKey> for PgpHash {
+    type Error = Error;
+
+    fn try_from(key: &SignedSecretSubKey) -> Result<Self, Self::Error> {
+        PgpHash::try_from(&key.key)
+    }
+}
+
+/// A symmetrically encrypted message (e.g. `gpg -c`): the passphrase-derived key
+/// directly decrypts the data following the SKESK packet.
+impl TryFrom<(&SymKeyEncryptedSessionKey, &Edata)> for PgpHash {
+    type Error = Error;
+
+    fn try_from((esk, edata): (&SymKeyEncryptedSessionKey, &Edata)) -> Result<Self, Self::Error> {
+        if esk.encrypted_key().is_some() {
+            return Err(Error::UnsupportedS2k(
+                "SKESK with an encrypted session key".into(),
+            ));
+        }
+        let usage = match edata {
+            Edata::SymEncryptedData(_) => Usage::Nine,
+            Edata::SymEncryptedProtectedData(_) => Usage::Eighteen,
+        };
+        let mut data = edata.data().to_vec();
+        if data.len() > MAX_SYMMETRIC_DATA_LEN {
+            log::warn!(
+                "truncating {} bytes of encrypted data to {MAX_SYMMETRIC_DATA_LEN}",
+                data.len()
+            );
+            data.truncate(MAX_SYMMETRIC_DATA_LEN);
+        }
+        let (s2k, hash_algorithm, count, salt) = s2k_params(esk.s2k())?;
+
+        Ok(PgpHash {
+            algorithm: Algorithm::Symmetric,
+            data_len: data.len(),
+            bits: None,
+            data,
+            s2k,
+            usage,
+            hash_algorithm,
+            cipher_algorithm: CipherAlgorithm::try_from(esk.sym_algorithm())?,
+            iv_len: None,
+            iv: None,
+            count,
+            salt,
+            extra_data: None,
+        })
+    }
+}
 
 /// This function gets everything needed to output a PgpHash:
 /// - public parameters: yields algorithm and bits
@@ -12,19 +94,43 @@ use std::error::Error;
 ///   - iv and length
 ///   - s2k parameters: s2k, usage, count, salt
 ///   - hash and cipher algorithms
-pub(crate) fn secretkey_to_pgphash(key: SecretKey) -> Result<PgpHash, Box<dyn Error>> {
-    helper(key.public_params(), key.secret_params())
-}
+fn helper(public_params: &PublicParams, secret_params: &SecretParams) -> Result<PgpHash, Error> {
+    let (algorithm, bits) = public_key_size(public_params);
+    match secret_params {
+        pgp::types::SecretParams::Plain(_) => Err(Error::Unprotected),
+        pgp::types::SecretParams::Encrypted(params) => {
+            let data = params.data().to_vec();
+            let iv = Some(params.iv().to_vec());
+            let iv_len = iv.as_ref().map(|z| z.len());
+            let cipher_algorithm = CipherAlgorithm::try_from(params.encryption_algorithm())?;
+            let usage = Usage::from_repr(params.string_to_key_id() as i32).ok_or_else(|| {
+                Error::UnsupportedS2k(format!("usage {}", params.string_to_key_id()))
+            })?;
 
-pub(crate) fn secretsubkey_to_pgphash(key: SecretSubkey) -> Result<PgpHash, Box<dyn Error>> {
-    helper(key.public_params(), key.secret_params())
+            let (s2k, hash_algorithm, count, salt) = s2k_params(params.string_to_key())?;
+
+            Ok(PgpHash {
+                algorithm,
+                data_len: data.len(),
+                bits: Some(bits),
+                data,
+                s2k,
+                usage,
+                hash_algorithm,
+                cipher_algorithm,
+                iv_len,
+                iv,
+                count,
+                salt,
+                extra_data: None,
+            })
+        }
+    }
 }
 
-fn helper(
-    public_params: &PublicParams,
-    secret_params: &SecretParams,
-) -> Result<PgpHash, Box<dyn Error>> {
-    let (algorithm, bits) = match public_params {
+/// The algorithm and the number of bits of a public key, as given in the hash
+pub(crate) fn public_key_size(public_params: &PublicParams) -> (Algorithm, usize) {
+    match public_params {
         pgp::types::PublicParams::RSA { n, e: _ } => {
             (Algorithm::RSAEncSign, n.as_bytes().len() * 8)
         }
@@ -54,60 +160,43 @@ fn helper(
             (Algorithm::ElGamal, p.as_bytes().len() * 8)
         }
         pgp::types::PublicParams::EdDSA { curve: _, q } => (Algorithm::EC, q.as_bytes().len() * 8),
-    };
-    match secret_params {
-        pgp::types::SecretParams::Plain(_) => {
-            Err("unexpectedly found plaintext secret parameters!".into())
-        }
-        pgp::types::SecretParams::Encrypted(params) => {
-            let data = params.data().to_vec();
-            let iv = Some(params.iv().to_vec());
-            let iv_len = iv.as_ref().map(|z| z.len());
-            let cipher_algorithm = CipherAlgorithm::try_from(params.encryption_algorithm())?;
-            let usage = Usage::from_repr(params.string_to_key_id() as i32)
-                .ok_or("invalid 'usage' parameter from string_to_key_id")?;
-
-            let s2k_obj = params.string_to_key();
-            let s2k = match s2k_obj.typ() {
-                pgp::types::StringToKeyType::Simple => StringToKey::Simple,
-                pgp::types::StringToKeyType::Salted => StringToKey::Salted,
-                pgp::types::StringToKeyType::IteratedAndSalted => StringToKey::IteratedSalted,
-                _ => return Err("invalid s2k type".into()),
-            };
-            let salt = match s2k_obj.salt() {
-                None => None,
-                Some(z) => z.try_into().ok(),
-            };
-            let hash_algorithm = match s2k_obj.hash() {
-                pgp::crypto::hash::HashAlgorithm::MD5 => HashAlgorithm::MD5,
-                pgp::crypto::hash::HashAlgorithm::SHA1 => HashAlgorithm::SHA1,
-                pgp::crypto::hash::HashAlgorithm::RIPEMD160 => HashAlgorithm::RIPEMD160,
-                pgp::crypto::hash::HashAlgorithm::SHA2_256 => HashAlgorithm::SHA256,
-                pgp::crypto::hash::HashAlgorithm::SHA2_384 => HashAlgorithm::SHA384,
-                pgp::crypto::hash::HashAlgorithm::SHA2_512 => HashAlgorithm::SHA512,
-                pgp::crypto::hash::HashAlgorithm::SHA2_224 => HashAlgorithm::SHA224,
-                // pgp::crypto::hash::HashAlgorithm::None => HashAlgorithm::Unknown,
-                // pgp::crypto::hash::HashAlgorithm::SHA3_256 => HashAlgorithm::SHA3_256,
-                // pgp::crypto::hash::HashAlgorithm::SHA3_512 => HashAlgorithm::SHA3_512,
-                // pgp::crypto::hash::HashAlgorithm::Private10 => HashAlgorithm::Private10,
-                _ => HashAlgorithm::Unknown,
-            };
-
-            Ok(PgpHash {
-                algorithm,
-                data_len: data.len(),
-                bits: Some(bits),
-                data,
-                s2k,
-                usage,
-                hash_algorithm,
-                cipher_algorithm,
-                iv_len,
-                iv,
-                count: s2k_obj.count(),
-                salt,
-                extra_data: None,
-            })
-        }
     }
 }
+
+/// s2k type, hash algorithm, count and salt
+type S2kParams = (StringToKey, HashAlgorithm, Option<usize>, Option<[u8; 8]>);
+
+/// Converts the string-to-key specifier to the s2k type, hash, count and salt of the hash.
+/// Salted (non-iterated) S2K has a count of 0 in the john format.
+fn s2k_params(s2k_obj: &pgp::types::StringToKey) -> Result<S2kParams, Error> {
+    let s2k = match s2k_obj.typ() {
+        pgp::types::StringToKeyType::Simple => StringToKey::Simple,
+        pgp::types::StringToKeyType::Salted => StringToKey::Salted,
+        pgp::types::StringToKeyType::IteratedAndSalted => StringToKey::IteratedSalted,
+        t => return Err(Error::UnsupportedS2k(format!("type {t:?}"))),
+    };
+    let salt = match s2k_obj.salt() {
+        None => None,
+        Some(z) => z.try_into().ok(),
+    };
+    let count = match s2k {
+        StringToKey::Simple => None,
+        StringToKey::Salted => Some(0),
+        StringToKey::IteratedSalted => s2k_obj.count(),
+    };
+    let hash_algorithm = match s2k_obj.hash() {
+        pgp::crypto::hash::HashAlgorithm::MD5 => HashAlgorithm::MD5,
+        pgp::crypto::hash::HashAlgorithm::SHA1 => HashAlgorithm::SHA1,
+        pgp::crypto::hash::HashAlgorithm::RIPEMD160 => HashAlgorithm::RIPEMD160,
+        pgp::crypto::hash::HashAlgorithm::SHA2_256 => HashAlgorithm::SHA256,
+        pgp::crypto::hash::HashAlgorithm::SHA2_384 => HashAlgorithm::SHA384,
+        pgp::crypto::hash::HashAlgorithm::SHA2_512 => HashAlgorithm::SHA512,
+        pgp::crypto::hash::HashAlgorithm::SHA2_224 => HashAlgorithm::SHA224,
+        // pgp::crypto::hash::HashAlgorithm::None => HashAlgorithm::Unknown,
+        // pgp::crypto::hash::HashAlgorithm::SHA3_256 => HashAlgorithm::SHA3_256,
+        // pgp::crypto::hash::HashAlgorithm::SHA3_512 => HashAlgorithm::SHA3_512,
+        // pgp::crypto::hash::HashAlgorithm::Private10 => HashAlgorithm::Private10,
+        _ => HashAlgorithm::Unknown,
+    };
+    Ok((s2k, hash_algorithm, count, salt))
+}
@@ -0,0 +1,242 @@
+use crate::{
+    error::Error,
+    hash::{Algorithm, CipherAlgorithm, HashAlgorithm, Named, PgpHash, StringToKey, Usage},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// The work to test a single candidate passphrase against a hash, as [`PgpHash::verify`] does it.
+///
+/// The string-to-key function dominates: every digest context hashes `count` bytes of repeated
+/// salt and passphrase (or the salt and passphrase once, if they are longer), and keys longer
+/// than the digest need further contexts, preloaded with one, two, ... zero bytes. The candidate
+/// is then checked by decrypting all encrypted data of a key, and for usage 254 by the SHA-1 hash
+/// of the key material. Of a message, only the random prefix and the two quick check bytes are
+/// decrypted; the rest of the message and its SHA-1 MDC (usage 18) only if the quick check bytes
+/// match, so they only add the average cost over all candidates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cost {
+    pub digest: HashAlgorithm,
+    pub cipher: CipherAlgorithm,
+    /// Bytes hashed by each digest context, without the preloaded zero bytes
+    pub hashed_bytes: usize,
+    /// Digest contexts needed for a key of the cipher's key size
+    pub contexts: usize,
+    /// Calls of the compression function of `digest` by the string-to-key function
+    pub s2k_blocks: u64,
+    /// Blocks decrypted with `cipher`, on average
+    pub cipher_blocks: f64,
+    /// Calls of the SHA-1 compression function to check the decrypted data, on average
+    pub check_blocks: f64,
+}
+
+impl Cost {
+    /// The cost of a candidate of `passphrase_len` bytes. Returns an error if the hash uses an
+    /// unknown digest or cipher.
+    pub fn of(hash: &PgpHash, passphrase_len: usize) -> Result<Self, Error> {
+        let unknown = |what: &str| Error::UnsupportedAlgorithm(format!("unknown {what}"));
+        let digest_size = hash
+            .hash_algorithm
+            .digest_size()
+            .ok_or_else(|| unknown("digest"))?;
+        let key_size = hash
+            .cipher_algorithm
+            .key_size()
+            .ok_or_else(|| unknown("cipher"))?;
+        let cipher_block = hash.cipher_algorithm.block_size().unwrap();
+
+        let salted = 8 + passphrase_len;
+        let hashed_bytes = match hash.s2k {
+            StringToKey::Simple => passphrase_len,
+            StringToKey::Salted => salted,
+            StringToKey::IteratedSalted => hash.count.unwrap_or_default().max(salted),
+        };
+        let contexts = key_size.div_ceil(digest_size);
+        let s2k_blocks = (0..contexts)
+            .map(|preload| blocks(hash.hash_algorithm, preload + hashed_bytes))
+            .sum();
+
+        let sha1 = |n| match n {
+            0 => 0.0,
+            n => blocks(HashAlgorithm::SHA1, n) as f64,
+        };
+        let data_blocks = hash.data.len().div_ceil(cipher_block) as f64;
+        let cipher_blocks = match hash.algorithm {
+            // one in 65536 wrong candidates passes the quick check
+            Algorithm::Symmetric => {
+                (cipher_block + 2).div_ceil(cipher_block) as f64 + data_blocks / 65536.0
+            }
+            _ => data_blocks,
+        };
+        let check_blocks = match (hash.algorithm, hash.usage) {
+            // only data that holds the prefix, the quick check and an MDC packet is checked by
+            // its MDC
+            (Algorithm::Symmetric, Usage::Eighteen) if hash.data.len() >= cipher_block + 24 => {
+                sha1(hash.data.len() - 20) / 65536.0
+            }
+            (_, Usage::TwoFiveFour) => sha1(hash.data.len().saturating_sub(20)),
+            _ => 0.0,
+        };
+        Ok(Cost {
+            digest: hash.hash_algorithm,
+            cipher: hash.cipher_algorithm,
+            hashed_bytes,
+            contexts,
+            s2k_blocks,
+            cipher_blocks,
+            check_blocks,
+        })
+    }
+}
+
+/// Calls of the compression function to hash `len` bytes, including the padding with a one bit
+/// and the message length (8 bytes for 64-byte blocks, 16 bytes for 128-byte blocks)
+fn blocks(digest: HashAlgorithm, len: usize) -> u64 {
+    let block = digest.block_size().unwrap();
+    (len + 1 + block / 8).div_ceil(block) as u64
+}
+
+/// Measured speeds of this computer, with which a [`Cost`] is converted to guesses per second.
+///
+/// It is written by the `benchmark` subcommand (see [`Benchmark`](crate::benchmark::Benchmark))
+/// to a TOML file, with digests and ciphers named in lower case (e.g. `sha256`, `aes128`,
+/// `tripledes`):
+///
+/// ```toml
+/// threads = 8
+/// scaling = 7.4
+///
+/// [digests]
+/// sha1 = 9500000.0
+///
+/// [ciphers]
+/// cast5 = 12000000.0
+///
+/// [[measurements]]
+/// digest = "sha1"
+/// cipher = "cast5"
+/// count = 65536
+/// single_core = 9400.0
+/// all_cores = 69000.0
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Calibration {
+    /// The number of threads of the measurements on all cores
+    pub threads: usize,
+    /// The speed on all cores relative to a single core
+    pub scaling: f64,
+    /// Calls of the compression function per second on a single core, by digest
+    #[serde(default)]
+    pub digests: BTreeMap<String, f64>,
+    /// Blocks decrypted per second on a single core, by cipher
+    #[serde(default)]
+    pub ciphers: BTreeMap<String, f64>,
+    /// The string-to-key speeds the digest speeds were derived from
+    #[serde(default)]
+    pub measurements: Vec<Measurement>,
+}
+
+/// The measured speed of the string-to-key function for a digest, cipher and count
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Measurement {
+    pub digest: String,
+    pub cipher: String,
+    pub count: usize,
+    /// Candidates tested per second on a single core
+    pub single_core: f64,
+    /// Candidates tested per second on all cores
+    pub all_cores: f64,
+    /// Candidates tested per second on a single core with the reference implementation of the
+    /// string-to-key function, if it was measured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<f64>,
+}
+
+impl Calibration {
+    /// `pgp2hc/calibration.toml` in the user's configuration directory (`$XDG_CONFIG_HOME` or
+    /// `~/.config`), None if neither is set
+    pub fn default_path() -> Option<PathBuf> {
+        let config = match std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(config.join("pgp2hc").join("calibration.toml"))
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    /// The calibration at `path`, or at the default path if there is one. None if no path is
+    /// given and there is no file at the default path.
+    pub fn find(path: Option<&Path>) -> Result<Option<Self>, Error> {
+        match path {
+            Some(path) => Ok(Some(Self::open(path)?)),
+            None => match Self::default_path().filter(|p| p.exists()) {
+                Some(path) => Ok(Some(Self::open(path)?)),
+                None => Ok(None),
+            },
+        }
+    }
+
+    /// Write the calibration, creating the directory if needed
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        if let Some(dir) = path.as_ref().parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_toml())?;
+        Ok(())
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, Error> {
+        toml::from_str(toml).map_err(|e| Error::InvalidCalibration(e.message().to_string()))
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("a calibration is valid TOML")
+    }
+
+    pub fn digest_speed(&self, digest: HashAlgorithm) -> Option<f64> {
+        self.digests.get(&digest.name()).copied()
+    }
+
+    pub fn cipher_speed(&self, cipher: CipherAlgorithm) -> Option<f64> {
+        self.ciphers.get(&cipher.name()).copied()
+    }
+
+    /// Candidates tested per second on a single core, None if the digest, the cipher or SHA-1
+    /// (if it is needed for the check) haven't been measured
+    pub fn guesses_per_second(&self, cost: &Cost) -> Option<f64> {
+        let check = match cost.check_blocks {
+            n if n > 0.0 => n / self.digest_speed(HashAlgorithm::SHA1)?,
+            _ => 0.0,
+        };
+        let seconds = cost.s2k_blocks as f64 / self.digest_speed(cost.digest)?
+            + cost.cipher_blocks / self.cipher_speed(cost.cipher)?
+            + check;
+        Some(1.0 / seconds)
+    }
+
+    /// Candidates tested per second on all cores
+    pub fn guesses_per_second_all(&self, cost: &Cost) -> Option<f64> {
+        Some(self.guesses_per_second(cost)? * self.scaling)
+    }
+
+    /// Candidates tested per second against all of the hashes with `costs` at once, on
+    /// `threads` threads. Threads beyond those of the calibration are assumed not to help.
+    pub fn candidates_per_second(&self, costs: &[Cost], threads: usize) -> Option<f64> {
+        let seconds = costs
+            .iter()
+            .map(|c| self.guesses_per_second(c).map(|g| 1.0 / g))
+            .sum::<Option<f64>>()?;
+        let threads = threads.min(self.threads).max(1);
+        let scaling = match threads {
+            1 => 1.0,
+            n => self.scaling * n as f64 / self.threads.max(1) as f64,
+        };
+        Some(scaling / seconds)
+    }
+}
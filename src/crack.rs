@@ -0,0 +1,500 @@
+use crate::encoding::{encode_variants, Encoding};
+use crate::error::Error;
+use crate::hash::PgpHash;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    iter,
+    ops::Range,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Hashcat-style masks
+pub mod mask;
+/// Hashcat-style rules
+pub mod rules;
+/// Checkpoint files to resume interrupted attacks
+pub mod session;
+/// Typo variants of remembered passphrases
+pub mod typos;
+
+pub use crate::potfile::potfile_line;
+pub use mask::Mask;
+pub use rules::{load_rules, Rule};
+pub use session::Session;
+pub use typos::{typo_variants, KeyboardLayout};
+
+/// Number of candidates a thread takes from the shared source at once
+const BATCH_SIZE: usize = 16;
+
+/// Marks a worker that is not testing a batch
+const IDLE: u64 = u64::MAX;
+
+/// Tests passphrase candidates against a set of hashes on all CPU cores.
+pub struct Cracker {
+    hashes: Vec<PgpHash>,
+    threads: usize,
+    encodings: Vec<Encoding>,
+    status: Option<(Duration, StatusFn)>,
+    checkpoint: Option<(Duration, CheckpointFn)>,
+    stop: Option<Arc<AtomicBool>>,
+}
+
+type StatusFn = Box<dyn Fn(&Status) + Send + Sync>;
+type CheckpointFn = Box<dyn Fn(u64) + Send + Sync>;
+
+/// Statistics of a finished `Cracker::run`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// Number of candidates that were tested, including the positions of rejected rules
+    pub tested: u64,
+    /// Number of hashes whose passphrase was found
+    pub cracked: usize,
+    /// All candidates before this index have been tested, see [`Cracker::checkpoint`]
+    pub position: u64,
+}
+
+/// Progress of a running `Cracker::run`, passed to the status callback
+#[derive(Clone, Debug)]
+pub struct Status {
+    /// Number of candidates tested so far, including the positions of rejected rules
+    pub tested: u64,
+    /// Number of hashes cracked so far
+    pub cracked: usize,
+    pub elapsed: Duration,
+}
+
+impl Status {
+    /// Candidates per second
+    pub fn rate(&self) -> f64 {
+        self.tested as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Estimated time until all of `keyspace` candidates are tested
+    pub fn eta(&self, keyspace: u64) -> Option<Duration> {
+        let rate = self.rate();
+        (rate > 0.0)
+            .then(|| Duration::from_secs_f64(keyspace.saturating_sub(self.tested) as f64 / rate))
+    }
+}
+
+impl Default for Cracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cracker {
+    /// A cracker without hashes, using one thread per CPU core
+    pub fn new() -> Self {
+        Self {
+            hashes: Vec::new(),
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            encodings: vec![Encoding::Utf8],
+            status: None,
+            checkpoint: None,
+            stop: None,
+        }
+    }
+
+    /// Try every candidate in each of `encodings` (only UTF-8 by default)
+    pub fn encodings(mut self, encodings: &[Encoding]) -> Self {
+        self.encodings = encodings.to_vec();
+        self
+    }
+
+    /// Call `report` every `interval` while `run` is testing candidates
+    pub fn status(
+        mut self,
+        interval: Duration,
+        report: impl Fn(&Status) + Send + Sync + 'static,
+    ) -> Self {
+        self.status = Some((interval, Box::new(report)));
+        self
+    }
+
+    /// Call `save` every `interval` while `run` is testing candidates, and once more when it
+    /// returns, with the position up to which all candidates have been tested.
+    /// [`Attack::positions_from`] resumes the attack from there.
+    pub fn checkpoint(
+        mut self,
+        interval: Duration,
+        save: impl Fn(u64) + Send + Sync + 'static,
+    ) -> Self {
+        self.checkpoint = Some((interval, Box::new(save)));
+        self
+    }
+
+    /// Stop `run` as soon as `flag` is set, e.g. by a Ctrl-C handler
+    pub fn stop_on(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.stop = Some(flag);
+        self
+    }
+
+    /// Use `threads` worker threads instead of one per CPU core
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Add a hash to attack. Returns false if the same hash was already added, and an error if
+    /// it can't be verified natively (e.g. because of an unknown cipher).
+    pub fn add(&mut self, hash: PgpHash) -> Result<bool, Error> {
+        if self.hashes.contains(&hash) {
+            return Ok(false);
+        }
+        hash.verifier()?;
+        self.hashes.push(hash);
+        Ok(true)
+    }
+
+    /// The hashes that were added
+    pub fn hashes(&self) -> &[PgpHash] {
+        &self.hashes
+    }
+
+    /// Test all `candidates` against the hashes, until the candidates are exhausted or all
+    /// passphrases have been found. `on_crack` is called once for every cracked hash, with the
+    /// passphrase bytes that matched and the encoding that produced them.
+    ///
+    /// The candidates are either passphrases or, like [`Positions`], optional passphrases, whose
+    /// None items are positions without a candidate, e.g. rejected by a rule.
+    pub fn run<I, C, F>(&self, candidates: I, on_crack: F) -> Result<Summary, Error>
+    where
+        I: Iterator<Item = io::Result<C>> + Send,
+        C: Into<Option<Vec<u8>>> + Send,
+        F: FnMut(&PgpHash, &[u8], Encoding) + Send,
+    {
+        // the candidates and the number of candidates taken from them
+        let source = Mutex::new((candidates, 0u64));
+        let on_crack = Mutex::new(on_crack);
+        let error = Mutex::new(None);
+        let cracked: Vec<_> = self.hashes.iter().map(|_| AtomicBool::new(false)).collect();
+        let remaining = AtomicUsize::new(self.hashes.len());
+        let tested = AtomicU64::new(0);
+        let finished = AtomicBool::new(false);
+        // the position of the batch each worker is testing
+        let batches: Vec<_> = (0..self.threads).map(|_| AtomicU64::new(IDLE)).collect();
+        let stopped = || {
+            self.stop
+                .as_ref()
+                .is_some_and(|s| s.load(Ordering::Relaxed))
+        };
+        let position = || {
+            let taken = source.lock().unwrap().1;
+            batches
+                .iter()
+                .map(|b| b.load(Ordering::Relaxed))
+                .fold(taken, u64::min)
+        };
+        let start = Instant::now();
+
+        thread::scope(|s| {
+            if self.status.is_some() || self.checkpoint.is_some() {
+                s.spawn(|| {
+                    let mut next_status = self.status.as_ref().map(|(i, _)| *i);
+                    let mut next_checkpoint = self.checkpoint.as_ref().map(|(i, _)| *i);
+                    while !finished.load(Ordering::Relaxed) {
+                        thread::sleep(Duration::from_millis(50));
+                        let elapsed = start.elapsed();
+                        if let (Some(next), Some((interval, report))) =
+                            (&mut next_status, &self.status)
+                        {
+                            if elapsed >= *next {
+                                *next += *interval;
+                                report(&Status {
+                                    tested: tested.load(Ordering::Relaxed),
+                                    cracked: self.hashes.len() - remaining.load(Ordering::Relaxed),
+                                    elapsed,
+                                });
+                            }
+                        }
+                        if let (Some(next), Some((interval, save))) =
+                            (&mut next_checkpoint, &self.checkpoint)
+                        {
+                            if elapsed >= *next {
+                                *next += *interval;
+                                save(position());
+                            }
+                        }
+                    }
+                });
+            }
+            let workers: Vec<_> = batches
+                .iter()
+                .map(|batch_position| {
+                    let (source, on_crack, error) = (&source, &on_crack, &error);
+                    let (cracked, remaining, tested) = (&cracked, &remaining, &tested);
+                    let stopped = &stopped;
+                    s.spawn(move || {
+                        // every hash was verified once when it was added
+                        let mut verifiers: Vec<_> = self
+                            .hashes
+                            .iter()
+                            .map(|h| h.verifier().expect("verifiable hash"))
+                            .collect();
+                        'batches: while remaining.load(Ordering::Relaxed) > 0 && !stopped() {
+                            let batch = {
+                                let mut source = source.lock().unwrap();
+                                let (candidates, taken) = &mut *source;
+                                let batch: io::Result<Vec<_>> =
+                                    candidates.by_ref().take(BATCH_SIZE).collect();
+                                match batch {
+                                    Ok(batch) if !batch.is_empty() => {
+                                        batch_position.store(*taken, Ordering::Relaxed);
+                                        *taken += batch.len() as u64;
+                                        batch
+                                    }
+                                    Ok(_) => break,
+                                    Err(e) => {
+                                        *error.lock().unwrap() = Some(e);
+                                        remaining.store(0, Ordering::Relaxed);
+                                        break;
+                                    }
+                                }
+                            };
+                            for candidate in batch {
+                                // an interrupted batch stays in progress, it is tested again
+                                // when the attack is resumed
+                                if stopped() {
+                                    break 'batches;
+                                }
+                                tested.fetch_add(1, Ordering::Relaxed);
+                                let Some(candidate) = candidate.into() else {
+                                    continue;
+                                };
+                                let variants = encode_variants(&candidate, &self.encodings);
+                                let targets = self.hashes.iter().zip(&mut verifiers);
+                                for ((hash, verifier), done) in targets.zip(cracked) {
+                                    let found = variants.iter().find(|(_, bytes)| {
+                                        !done.load(Ordering::Relaxed)
+                                            && verifier.verify_bytes(bytes).unwrap_or(false)
+                                    });
+                                    let Some((encoding, bytes)) = found else {
+                                        continue;
+                                    };
+                                    if !done.swap(true, Ordering::Relaxed) {
+                                        (on_crack.lock().unwrap())(hash, bytes, *encoding);
+                                        remaining.fetch_sub(1, Ordering::Relaxed);
+                                    }
+                                }
+                            }
+                            batch_position.store(IDLE, Ordering::Relaxed);
+                        }
+                    })
+                })
+                .collect();
+            for worker in workers {
+                // a panicking worker is re-raised when the scope ends
+                let _ = worker.join();
+            }
+            finished.store(true, Ordering::Relaxed);
+        });
+
+        let position = position();
+        if let Some((_, save)) = &self.checkpoint {
+            save(position);
+        }
+        if let Some(e) = error.into_inner().unwrap() {
+            return Err(e.into());
+        }
+        Ok(Summary {
+            tested: tested.into_inner(),
+            cracked: cracked.iter().filter(|c| c.load(Ordering::Relaxed)).count(),
+            position,
+        })
+    }
+}
+
+/// Boxed iterator over passphrase candidates
+pub type Candidates<'a> = Box<dyn Iterator<Item = io::Result<Vec<u8>>> + Send + 'a>;
+
+/// Boxed iterator over the positions of an attack's keyspace, with the candidate at each
+/// position or None if a rule rejected it
+pub type Positions<'a> = Box<dyn Iterator<Item = io::Result<Option<Vec<u8>>>> + Send + 'a>;
+
+/// A wordlist file. Its lines are counted when it is opened, so that the keyspace is known.
+#[derive(Clone, Debug)]
+pub struct WordlistFile {
+    path: PathBuf,
+    len: u64,
+}
+
+impl WordlistFile {
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        let mut len = 0;
+        for line in BufReader::new(File::open(&path)?).split(b'\n') {
+            line?;
+            len += 1;
+        }
+        Ok(Self { path, len })
+    }
+
+    /// Number of words
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Read the words from the start of the file
+    pub fn words(&self) -> Result<Candidates<'static>, Error> {
+        Ok(Box::new(wordlist(BufReader::new(File::open(&self.path)?))))
+    }
+}
+
+/// The candidates to test, in a fixed order.
+/// Hybrid attacks go through the wordlist once, combining every word with all mask candidates.
+#[derive(Clone, Debug)]
+pub enum Attack {
+    Wordlist(WordlistFile),
+    /// Every rule applied to every word. A rejected candidate keeps its position, so word `i`
+    /// and rule `j` are at position `i * rules + j`.
+    WordlistRules(WordlistFile, Vec<Rule>),
+    /// The masks one after another, e.g. the increments of a mask
+    Mask(Vec<Mask>),
+    /// Every word with every mask candidate appended
+    HybridWordlistMask(WordlistFile, Vec<Mask>),
+    /// Every word with every mask candidate prepended
+    HybridMaskWordlist(Vec<Mask>, WordlistFile),
+    /// A fixed list of candidates, e.g. the typo variants of a passphrase
+    List(Vec<Vec<u8>>),
+}
+
+impl Attack {
+    /// Total number of candidates, or None if it doesn't fit into a `u64`
+    pub fn keyspace(&self) -> Option<u64> {
+        let masks = |masks: &[Mask]| {
+            masks
+                .iter()
+                .try_fold(0u64, |n, m| n.checked_add(m.keyspace()?))
+        };
+        match self {
+            Attack::Wordlist(words) => Some(words.len()),
+            Attack::WordlistRules(words, rules) => words.len().checked_mul(rules.len() as u64),
+            Attack::Mask(m) => masks(m),
+            Attack::HybridWordlistMask(words, m) | Attack::HybridMaskWordlist(m, words) => {
+                masks(m)?.checked_mul(words.len())
+            }
+            Attack::List(list) => Some(list.len() as u64),
+        }
+    }
+
+    /// The candidate positions of part `node` (counted from 1) of `nodes` equal parts of the
+    /// keyspace, to split the attack across machines. The parts don't overlap and together
+    /// cover the whole keyspace. None if the keyspace is unknown.
+    pub fn node_range(&self, node: u64, nodes: u64) -> Option<Range<u64>> {
+        let keyspace = u128::from(self.keyspace()?);
+        let bound = |i: u64| (keyspace * u128::from(i) / u128::from(nodes.max(1))) as u64;
+        Some(bound(node.saturating_sub(1))..bound(node.min(nodes)))
+    }
+
+    /// Generate all candidates
+    pub fn candidates(&self) -> Result<Candidates<'_>, Error> {
+        self.candidates_from(0)
+    }
+
+    /// Generate the candidates from `position` on, leaving out the positions of rejected rules
+    pub fn candidates_from(&self, position: u64) -> Result<Candidates<'_>, Error> {
+        Ok(Box::new(
+            self.positions_from(position)?.filter_map(Result::transpose),
+        ))
+    }
+
+    /// Generate the positions of the keyspace from `position` on, e.g. to resume the attack
+    /// from a checkpoint or to test a slice of it. Masks are started at the position directly,
+    /// words before it are read and skipped.
+    pub fn positions_from(&self, position: u64) -> Result<Positions<'_>, Error> {
+        let skip = usize::try_from(position).unwrap_or(usize::MAX);
+        let candidates: Candidates = match self {
+            Attack::Wordlist(words) => Box::new(words.words()?.skip(skip)),
+            Attack::WordlistRules(words, rules) => {
+                // like hybrid attacks, skip whole words and start the first one at its rule
+                let per_word = rules.len().max(1) as u64;
+                let skip = usize::try_from(position / per_word).unwrap_or(usize::MAX);
+                let first = (position % per_word) as usize;
+                return Ok(Box::new(
+                    apply_rules(Box::new(words.words()?.skip(skip)), rules).skip(first),
+                ));
+            }
+            Attack::Mask(m) => Box::new(all_candidates(m, position).map(Ok)),
+            Attack::List(list) => Box::new(list.iter().skip(skip).cloned().map(Ok)),
+            Attack::HybridWordlistMask(words, m) => hybrid(words, m, position, true)?,
+            Attack::HybridMaskWordlist(m, words) => hybrid(words, m, position, false)?,
+        };
+        Ok(Box::new(candidates.map(|c| c.map(Some))))
+    }
+}
+
+/// Every word combined with every mask candidate, from `position` on
+fn hybrid<'a>(
+    words: &WordlistFile,
+    masks: &'a [Mask],
+    position: u64,
+    append: bool,
+) -> Result<Candidates<'a>, Error> {
+    // the mask candidates of each word come one after the other
+    let per_word = masks
+        .iter()
+        .try_fold(0u64, |n, m| n.checked_add(m.keyspace()?))
+        .unwrap_or(u64::MAX)
+        .max(1);
+    let skip = usize::try_from(position / per_word).unwrap_or(usize::MAX);
+    let first = position % per_word;
+    Ok(Box::new(words.words()?.skip(skip).enumerate().flat_map(
+        move |(i, word)| -> Candidates<'a> {
+            let start = if i == 0 { first } else { 0 };
+            match word {
+                Ok(word) => Box::new(all_candidates(masks, start).map(move |c| {
+                    Ok(match append {
+                        true => [&word[..], &c].concat(),
+                        false => [&c, &word[..]].concat(),
+                    })
+                })),
+                Err(e) => Box::new(iter::once(Err(e))),
+            }
+        },
+    )))
+}
+
+/// Apply every rule to every word. Rejected candidates are None, so that every word takes up
+/// one position per rule.
+pub fn apply_rules<'a>(words: Candidates<'a>, rules: &'a [Rule]) -> Positions<'a> {
+    Box::new(words.flat_map(move |word| -> Positions<'a> {
+        match word {
+            Ok(word) => Box::new(rules.iter().map(move |r| Ok(r.apply(&word)))),
+            Err(e) => Box::new(iter::once(Err(e))),
+        }
+    }))
+}
+
+/// The candidates of all masks, one mask after the other, from `start` on
+fn all_candidates(masks: &[Mask], mut start: u64) -> impl Iterator<Item = Vec<u8>> + Send + '_ {
+    masks.iter().flat_map(move |m| {
+        let keyspace = m.keyspace().unwrap_or(u64::MAX);
+        let from = start.min(keyspace);
+        start -= from;
+        m.iter_from(from)
+    })
+}
+
+/// Read a wordlist with one candidate per line (LF or CRLF line endings)
+pub fn wordlist(reader: impl BufRead) -> impl Iterator<Item = io::Result<Vec<u8>>> {
+    reader.split(b'\n').map(|line| {
+        line.map(|mut l| {
+            if l.last() == Some(&b'\r') {
+                l.pop();
+            }
+            l
+        })
+    })
+}
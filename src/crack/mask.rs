@@ -0,0 +1,172 @@
+use crate::error::Error;
+
+const LOWER: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+const UPPER: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &[u8] = b"0123456789";
+const SPECIAL: &[u8] = b" !\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+/// A hashcat-style mask: one set of allowed bytes per position.
+///
+/// Supported placeholders are `?l`, `?u`, `?d`, `?h`, `?H`, `?s`, `?a`, `?b`, the custom
+/// charsets `?1` to `?4`, and `??` for a literal question mark. Any other character stands
+/// for itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mask {
+    positions: Vec<Vec<u8>>,
+}
+
+impl Mask {
+    /// Parse `mask`, where `custom[i]` defines the charset `?{i+1}`. Custom charsets may
+    /// use the built-in placeholders, e.g. `?l?d_`.
+    pub fn parse(mask: &str, custom: &[&str]) -> Result<Self, Error> {
+        if custom.len() > 4 {
+            return Err(Error::InvalidMask("at most 4 custom charsets".into()));
+        }
+        let custom = custom
+            .iter()
+            .map(|c| {
+                let mut set = Vec::new();
+                for part in tokens(c, &[])? {
+                    for b in part {
+                        if !set.contains(&b) {
+                            set.push(b);
+                        }
+                    }
+                }
+                Ok(set)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let positions = tokens(mask, &custom)?;
+        if positions.is_empty() {
+            return Err(Error::InvalidMask("empty mask".into()));
+        }
+        Ok(Self { positions })
+    }
+
+    /// Number of positions, i.e. the length of the candidates in bytes
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Number of candidates, or None if it doesn't fit into a `u64`
+    pub fn keyspace(&self) -> Option<u64> {
+        self.positions
+            .iter()
+            .try_fold(1u64, |n, set| n.checked_mul(set.len() as u64))
+    }
+
+    /// The masks made of the first `min` to `max` positions, for incremental attacks
+    pub fn increments(&self, min: usize, max: usize) -> Vec<Mask> {
+        (min.max(1)..=max.min(self.len()))
+            .map(|n| Mask {
+                positions: self.positions[..n].to_vec(),
+            })
+            .collect()
+    }
+
+    /// The candidate at `index`, counting with the last position changing fastest
+    pub fn candidate(&self, mut index: u64) -> Vec<u8> {
+        let mut candidate = vec![0; self.len()];
+        for (c, set) in candidate.iter_mut().zip(&self.positions).rev() {
+            let n = set.len() as u64;
+            *c = set[(index % n) as usize];
+            index /= n;
+        }
+        candidate
+    }
+
+    /// All candidates from `start` on, in the order of `candidate`
+    pub fn iter_from(&self, start: u64) -> MaskIter<'_> {
+        let mut digits = vec![0; self.len()];
+        let mut index = start;
+        for (d, set) in digits.iter_mut().zip(&self.positions).rev() {
+            let n = set.len() as u64;
+            *d = (index % n) as usize;
+            index /= n;
+        }
+        MaskIter {
+            mask: self,
+            digits,
+            // an index beyond the keyspace wraps around in the digits
+            done: index > 0,
+        }
+    }
+}
+
+/// Iterator over the candidates of a `Mask`
+pub struct MaskIter<'a> {
+    mask: &'a Mask,
+    digits: Vec<usize>,
+    done: bool,
+}
+
+impl Iterator for MaskIter<'_> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        if self.done {
+            return None;
+        }
+        let candidate = self
+            .digits
+            .iter()
+            .zip(&self.mask.positions)
+            .map(|(&d, set)| set[d])
+            .collect();
+        // increment like an odometer, finished when the first position wraps around
+        self.done = true;
+        for (d, set) in self.digits.iter_mut().zip(&self.mask.positions).rev() {
+            *d += 1;
+            if *d < set.len() {
+                self.done = false;
+                break;
+            }
+            *d = 0;
+        }
+        Some(candidate)
+    }
+}
+
+/// Split a mask into the byte sets of its positions
+fn tokens(mask: &str, custom: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, Error> {
+    let mut positions = Vec::new();
+    let mut bytes = mask.bytes();
+    while let Some(b) = bytes.next() {
+        if b != b'?' {
+            positions.push(vec![b]);
+            continue;
+        }
+        let set = match bytes.next() {
+            Some(b'l') => LOWER.to_vec(),
+            Some(b'u') => UPPER.to_vec(),
+            Some(b'd') => DIGITS.to_vec(),
+            Some(b'h') => b"0123456789abcdef".to_vec(),
+            Some(b'H') => b"0123456789ABCDEF".to_vec(),
+            Some(b's') => SPECIAL.to_vec(),
+            Some(b'a') => [LOWER, UPPER, DIGITS, SPECIAL].concat(),
+            Some(b'b') => (0..=255).collect(),
+            Some(b'?') => vec![b'?'],
+            Some(c @ b'1'..=b'4') => {
+                custom.get(usize::from(c - b'1')).cloned().ok_or_else(|| {
+                    Error::InvalidMask(format!("custom charset ?{} is not defined", c as char))
+                })?
+            }
+            Some(c) => {
+                return Err(Error::InvalidMask(format!(
+                    "unknown placeholder ?{}",
+                    c as char
+                )))
+            }
+            None => return Err(Error::InvalidMask("mask ends with '?'".into())),
+        };
+        if set.is_empty() {
+            return Err(Error::InvalidMask("empty charset".into()));
+        }
+        positions.push(set);
+    }
+    Ok(positions)
+}
@@ -0,0 +1,303 @@
+use crate::error::Error;
+use std::io::BufRead;
+
+/// The maximal length of a candidate, hashcat's `RP_PASSWORD_SIZE`
+pub const MAX_LEN: usize = 256;
+
+/// A single rule function. Positions and lengths are in bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Op {
+    Noop,
+    Lower,
+    Upper,
+    Capitalize,
+    InvertCapitalize,
+    ToggleAll,
+    Toggle(usize),
+    Reverse,
+    Duplicate,
+    DuplicateN(usize),
+    Reflect,
+    RotateLeft,
+    RotateRight,
+    Append(u8),
+    Prepend(u8),
+    DeleteFirst,
+    DeleteLast,
+    Delete(usize),
+    Extract(usize, usize),
+    Omit(usize, usize),
+    Insert(usize, u8),
+    Overwrite(usize, u8),
+    Truncate(usize),
+    Replace(u8, u8),
+    Purge(u8),
+    DuplicateFirst(usize),
+    DuplicateLast(usize),
+    DuplicateAll,
+    SwapFront,
+    SwapBack,
+    Swap(usize, usize),
+    Increment(usize),
+    Decrement(usize),
+    ReplaceNext(usize),
+    ReplacePrevious(usize),
+    DuplicateBlockFront(usize),
+    DuplicateBlockBack(usize),
+    Title,
+    TitleSeparator(u8),
+    RejectGreater(usize),
+    RejectLess(usize),
+    RejectUnlessLength(usize),
+    RejectContains(u8),
+    RejectUnlessContains(u8),
+    RejectUnlessFirst(u8),
+    RejectUnlessLast(u8),
+    RejectUnlessAt(usize, u8),
+}
+
+/// A rule in hashcat syntax, e.g. `c $1 $!` or `sa@ so0`.
+///
+/// All functions of hashcat's rule-based attack are supported, together with the rejection rules
+/// `<`, `>`, `_`, `!`, `/`, `(`, `)` and `=` (with hashcat's meaning of `<N` and `>N`:
+/// reject words longer and shorter than N), except the bitwise shifts (`L`, `R`), the toggle
+/// after a separator (`3`), the memory functions (`M`, `4`, `6`, `X`, `Q`) and the rejection by
+/// count (`%`). Spaces between functions are ignored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rule {
+    ops: Vec<Op>,
+}
+
+impl Rule {
+    pub fn parse(rule: &str) -> Result<Self, Error> {
+        let mut bytes = rule.bytes();
+        let mut ops = Vec::new();
+        while let Some(f) = bytes.next() {
+            let mut byte = || {
+                bytes.next().ok_or_else(|| {
+                    Error::InvalidRule(format!("missing argument of '{}'", f as char))
+                })
+            };
+            let op = match f {
+                b' ' | b'\t' => continue,
+                b':' => Op::Noop,
+                b'l' => Op::Lower,
+                b'u' => Op::Upper,
+                b'c' => Op::Capitalize,
+                b'C' => Op::InvertCapitalize,
+                b't' => Op::ToggleAll,
+                b'T' => Op::Toggle(position(byte()?)?),
+                b'r' => Op::Reverse,
+                b'd' => Op::Duplicate,
+                b'p' => Op::DuplicateN(position(byte()?)?),
+                b'f' => Op::Reflect,
+                b'{' => Op::RotateLeft,
+                b'}' => Op::RotateRight,
+                b'$' => Op::Append(byte()?),
+                b'^' => Op::Prepend(byte()?),
+                b'[' => Op::DeleteFirst,
+                b']' => Op::DeleteLast,
+                b'D' => Op::Delete(position(byte()?)?),
+                b'x' => Op::Extract(position(byte()?)?, position(byte()?)?),
+                b'O' => Op::Omit(position(byte()?)?, position(byte()?)?),
+                b'i' => Op::Insert(position(byte()?)?, byte()?),
+                b'o' => Op::Overwrite(position(byte()?)?, byte()?),
+                b'\'' => Op::Truncate(position(byte()?)?),
+                b's' => Op::Replace(byte()?, byte()?),
+                b'@' => Op::Purge(byte()?),
+                b'z' => Op::DuplicateFirst(position(byte()?)?),
+                b'Z' => Op::DuplicateLast(position(byte()?)?),
+                b'q' => Op::DuplicateAll,
+                b'k' => Op::SwapFront,
+                b'K' => Op::SwapBack,
+                b'*' => Op::Swap(position(byte()?)?, position(byte()?)?),
+                b'+' => Op::Increment(position(byte()?)?),
+                b'-' => Op::Decrement(position(byte()?)?),
+                b'.' => Op::ReplaceNext(position(byte()?)?),
+                b',' => Op::ReplacePrevious(position(byte()?)?),
+                b'y' => Op::DuplicateBlockFront(position(byte()?)?),
+                b'Y' => Op::DuplicateBlockBack(position(byte()?)?),
+                b'E' => Op::Title,
+                b'e' => Op::TitleSeparator(byte()?),
+                b'<' => Op::RejectGreater(position(byte()?)?),
+                b'>' => Op::RejectLess(position(byte()?)?),
+                b'_' => Op::RejectUnlessLength(position(byte()?)?),
+                b'!' => Op::RejectContains(byte()?),
+                b'/' => Op::RejectUnlessContains(byte()?),
+                b'(' => Op::RejectUnlessFirst(byte()?),
+                b')' => Op::RejectUnlessLast(byte()?),
+                b'=' => Op::RejectUnlessAt(position(byte()?)?, byte()?),
+                b'L' | b'R' | b'3' | b'M' | b'4' | b'6' | b'X' | b'Q' | b'%' => {
+                    return Err(Error::UnsupportedRule(format!(
+                        "function '{}' is not implemented",
+                        f as char
+                    )))
+                }
+                f => {
+                    return Err(Error::InvalidRule(format!(
+                        "unknown function '{}'",
+                        f as char
+                    )))
+                }
+            };
+            ops.push(op);
+        }
+        Ok(Self { ops })
+    }
+
+    /// Apply the rule to `word`, None if the word is rejected.
+    /// Like in hashcat, functions with a position beyond the word leave it unchanged, and
+    /// words and candidates longer than [`MAX_LEN`] bytes are rejected.
+    pub fn apply(&self, word: &[u8]) -> Option<Vec<u8>> {
+        if word.len() > MAX_LEN {
+            return None;
+        }
+        let mut w = word.to_vec();
+        for op in &self.ops {
+            let len = w.len();
+            match *op {
+                Op::Noop => {}
+                Op::Lower => w.make_ascii_lowercase(),
+                Op::Upper => w.make_ascii_uppercase(),
+                Op::Capitalize => {
+                    w.make_ascii_lowercase();
+                    if let Some(c) = w.first_mut() {
+                        c.make_ascii_uppercase();
+                    }
+                }
+                Op::InvertCapitalize => {
+                    w.make_ascii_uppercase();
+                    if let Some(c) = w.first_mut() {
+                        c.make_ascii_lowercase();
+                    }
+                }
+                Op::ToggleAll => w.iter_mut().for_each(toggle),
+                Op::Toggle(n) => {
+                    if let Some(c) = w.get_mut(n) {
+                        toggle(c);
+                    }
+                }
+                Op::Reverse => w.reverse(),
+                Op::Duplicate => w = w.repeat(2),
+                Op::DuplicateN(n) => w = w.repeat(n + 1),
+                Op::Reflect => {
+                    let reversed: Vec<_> = w.iter().rev().copied().collect();
+                    w.extend(reversed);
+                }
+                Op::RotateLeft if len > 0 => w.rotate_left(1),
+                Op::RotateRight if len > 0 => w.rotate_right(1),
+                Op::RotateLeft | Op::RotateRight => {}
+                Op::Append(c) => w.push(c),
+                Op::Prepend(c) => w.insert(0, c),
+                Op::DeleteFirst if len > 0 => {
+                    w.remove(0);
+                }
+                Op::DeleteLast => {
+                    w.pop();
+                }
+                Op::Delete(n) if n < len => {
+                    w.remove(n);
+                }
+                Op::Extract(n, m) if n + m <= len => w = w[n..n + m].to_vec(),
+                Op::Omit(n, m) if n + m <= len => {
+                    w.drain(n..n + m);
+                }
+                Op::Insert(n, c) if n <= len => w.insert(n, c),
+                Op::Overwrite(n, c) if n < len => w[n] = c,
+                Op::Truncate(n) => w.truncate(n),
+                Op::Replace(x, y) => w.iter_mut().filter(|c| **c == x).for_each(|c| *c = y),
+                Op::Purge(x) => w.retain(|&c| c != x),
+                Op::DuplicateFirst(n) if len > 0 => {
+                    w.splice(0..0, std::iter::repeat_n(w[0], n));
+                }
+                Op::DuplicateLast(n) if len > 0 => w.extend(std::iter::repeat_n(w[len - 1], n)),
+                Op::DuplicateAll => w = w.iter().flat_map(|&c| [c, c]).collect(),
+                Op::SwapFront if len >= 2 => w.swap(0, 1),
+                Op::SwapBack if len >= 2 => w.swap(len - 2, len - 1),
+                Op::Swap(n, m) if n < len && m < len => w.swap(n, m),
+                Op::Increment(n) if n < len => w[n] = w[n].wrapping_add(1),
+                Op::Decrement(n) if n < len => w[n] = w[n].wrapping_sub(1),
+                Op::ReplaceNext(n) if n + 1 < len => w[n] = w[n + 1],
+                Op::ReplacePrevious(n) if n >= 1 && n < len => w[n] = w[n - 1],
+                Op::DuplicateBlockFront(n) if n <= len => {
+                    let block = w[..n].to_vec();
+                    w.splice(0..0, block);
+                }
+                Op::DuplicateBlockBack(n) if n <= len => {
+                    let block = w[len - n..].to_vec();
+                    w.extend(block);
+                }
+                Op::Title => title(&mut w, b' '),
+                Op::TitleSeparator(sep) => title(&mut w, sep),
+                Op::RejectGreater(n) if len > n => return None,
+                Op::RejectLess(n) if len < n => return None,
+                Op::RejectUnlessLength(n) if len != n => return None,
+                Op::RejectContains(c) if w.contains(&c) => return None,
+                Op::RejectUnlessContains(c) if !w.contains(&c) => return None,
+                Op::RejectUnlessFirst(c) if w.first() != Some(&c) => return None,
+                Op::RejectUnlessLast(c) if w.last() != Some(&c) => return None,
+                Op::RejectUnlessAt(n, c) if w.get(n) != Some(&c) => return None,
+                // out of range positions and rejection rules that pass
+                _ => {}
+            }
+            if w.len() > MAX_LEN {
+                return None;
+            }
+        }
+        Some(w)
+    }
+}
+
+/// Read rules in hashcat syntax, one per line. Empty lines and comments (`#`) are skipped, and
+/// so are rules with unsupported functions, like hashcat skips rules it can't apply.
+pub fn load_rules(reader: impl BufRead) -> Result<Vec<Rule>, Error> {
+    let mut rules = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match Rule::parse(line) {
+            Ok(rule) => rules.push(rule),
+            Err(Error::UnsupportedRule(e)) => log::warn!("skipping rule in line {}: {e}", i + 1),
+            Err(Error::InvalidRule(e)) => {
+                return Err(Error::InvalidRule(format!("line {}: {e}", i + 1)))
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(rules)
+}
+
+/// Positions are written as 0-9 and A-Z for 10-35
+fn position(c: u8) -> Result<usize, Error> {
+    match c {
+        b'0'..=b'9' => Ok(usize::from(c - b'0')),
+        b'A'..=b'Z' => Ok(usize::from(c - b'A') + 10),
+        c => Err(Error::InvalidRule(format!(
+            "invalid position '{}'",
+            c as char
+        ))),
+    }
+}
+
+fn toggle(c: &mut u8) {
+    if c.is_ascii_lowercase() {
+        c.make_ascii_uppercase()
+    } else {
+        c.make_ascii_lowercase()
+    }
+}
+
+/// Lowercase the word and uppercase the first letter and every letter after `sep`
+fn title(w: &mut [u8], sep: u8) {
+    w.make_ascii_lowercase();
+    let mut upper = true;
+    for c in w.iter_mut() {
+        if upper {
+            c.make_ascii_uppercase();
+        }
+        upper = *c == sep;
+    }
+}
@@ -0,0 +1,122 @@
+use super::potfile_line;
+use crate::{error::Error, hash::PgpHash};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+const HEADER: &str = "pgp2hc session 1";
+
+/// A cracking session, saved to a file so that an interrupted attack can be resumed.
+///
+/// The file records the attack, the position up to which all candidates have been tested
+/// (see [`Cracker::checkpoint`](super::Cracker::checkpoint)), and the hashes cracked so far.
+#[derive(Clone, Debug)]
+pub struct Session {
+    path: PathBuf,
+    attack: String,
+    position: u64,
+    /// potfile lines of the cracked hashes
+    cracked: Vec<String>,
+}
+
+impl Session {
+    /// Resume the session saved at `path`, or start a new one if the file doesn't exist.
+    /// `attack` describes the attack on a single line; a saved session can only be resumed
+    /// with the same attack.
+    pub fn open(path: impl Into<PathBuf>, attack: &str) -> Result<Self, Error> {
+        let path = path.into();
+        let mut session = Self {
+            path,
+            attack: attack.replace('\n', " "),
+            position: 0,
+            cracked: Vec::new(),
+        };
+        let text = match fs::read_to_string(&session.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(session),
+            Err(e) => return Err(e.into()),
+        };
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(Error::InvalidSession(format!(
+                "{} is not a session file",
+                session.path.display()
+            )));
+        }
+        for line in lines {
+            match line.split_once(' ') {
+                Some(("attack", attack)) if attack == session.attack => {}
+                Some(("attack", _)) => {
+                    return Err(Error::InvalidSession(format!(
+                        "{} was started with a different attack",
+                        session.path.display()
+                    )))
+                }
+                Some(("position", n)) => {
+                    session.position = n
+                        .parse()
+                        .map_err(|_| Error::InvalidSession(format!("invalid position '{n}'")))?
+                }
+                Some(("cracked", line)) => session.cracked.push(line.to_string()),
+                _ => {
+                    return Err(Error::InvalidSession(format!(
+                        "unexpected line '{line}' in {}",
+                        session.path.display()
+                    )))
+                }
+            }
+        }
+        Ok(session)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// All candidates of the attack before this position have been tested
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: u64) {
+        self.position = position;
+    }
+
+    /// The cracked hashes as potfile lines
+    pub fn cracked(&self) -> &[String] {
+        &self.cracked
+    }
+
+    /// Whether the passphrase of `hash` was found in this session
+    pub fn is_cracked(&self, hash: &PgpHash) -> bool {
+        let hash = hash.to_string();
+        self.cracked
+            .iter()
+            .any(|line| line.split_once(':').is_some_and(|(h, _)| h == hash))
+    }
+
+    pub fn add_cracked(&mut self, hash: &PgpHash, password: &[u8]) {
+        if !self.is_cracked(hash) {
+            self.cracked.push(potfile_line(hash, password));
+        }
+    }
+
+    /// Write the session to its file. The file is replaced atomically, so that a crash while
+    /// saving leaves the previous checkpoint intact.
+    pub fn save(&self) -> Result<(), Error> {
+        let mut text = format!(
+            "{HEADER}\nattack {}\nposition {}\n",
+            self.attack, self.position
+        );
+        for line in &self.cracked {
+            text += &format!("cracked {line}\n");
+        }
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, text)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
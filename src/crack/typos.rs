@@ -0,0 +1,233 @@
+use crate::error::Error;
+use std::collections::HashSet;
+
+const QWERTY: &str = "\
+1234567890-= !@#$%^&*()_+
+qwertyuiop[]\\ QWERTYUIOP{}|
+asdfghjkl;' ASDFGHJKL:\"
+zxcvbnm,./ ZXCVBNM<>?";
+
+const QWERTZ: &str = "\
+1234567890ß´ !\"§$%&/()=?`
+qwertzuiopü+ QWERTZUIOPÜ*
+asdfghjklöä# ASDFGHJKLÖÄ'
+yxcvbnm,.- YXCVBNM;:_";
+
+const AZERTY: &str = "\
+&é\"'(-è_çà)= 1234567890°+
+azertyuiop^$ AZERTYUIOP¨£
+qsdfghjklmù* QSDFGHJKLM%µ
+wxcvbn,;:! WXCVBN?./§";
+
+/// A keyboard layout for adjacent-key slips: rows of keys, unshifted and shifted.
+///
+/// Rows are assumed to be staggered like on a standard keyboard, each row starting half a key
+/// to the right of the row above, so that e.g. `s` is next to `w`, `e`, `a`, `d`, `z` and `x`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyboardLayout {
+    rows: Vec<Vec<char>>,
+    shifted: Vec<Vec<char>>,
+}
+
+impl KeyboardLayout {
+    /// US QWERTY
+    pub fn qwerty() -> Self {
+        Self::parse(QWERTY).unwrap()
+    }
+
+    /// German QWERTZ
+    pub fn qwertz() -> Self {
+        Self::parse(QWERTZ).unwrap()
+    }
+
+    /// French AZERTY
+    pub fn azerty() -> Self {
+        Self::parse(AZERTY).unwrap()
+    }
+
+    /// A built-in layout by name: `qwerty`, `qwertz` or `azerty`
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "qwerty" | "us" => Some(Self::qwerty()),
+            "qwertz" | "de" => Some(Self::qwertz()),
+            "azerty" | "fr" => Some(Self::azerty()),
+            _ => None,
+        }
+    }
+
+    /// Parse a layout with one row per line, from the top. Each line has the unshifted keys,
+    /// optionally followed by whitespace and the shifted keys of the same row.
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut rows = Vec::new();
+        let mut shifted = Vec::new();
+        for line in text.lines().filter(|l| !l.trim().is_empty()) {
+            let mut parts = line.split_whitespace();
+            let row: Vec<char> = parts.next().unwrap_or_default().chars().collect();
+            let shifted_row: Vec<char> = parts.next().unwrap_or_default().chars().collect();
+            if !shifted_row.is_empty() && shifted_row.len() != row.len() {
+                return Err(Error::InvalidLayout(format!(
+                    "row '{line}' has a different number of shifted and unshifted keys"
+                )));
+            }
+            rows.push(row);
+            shifted.push(shifted_row);
+        }
+        if rows.is_empty() {
+            return Err(Error::InvalidLayout("no rows".into()));
+        }
+        Ok(Self { rows, shifted })
+    }
+
+    /// The keys next to `c`, in the same shift state
+    pub fn neighbours(&self, c: char) -> Vec<char> {
+        for rows in [&self.rows, &self.shifted] {
+            for (r, row) in rows.iter().enumerate() {
+                let Some(col) = row.iter().position(|&k| k == c) else {
+                    continue;
+                };
+                let key = |r: Option<usize>, c: Option<usize>| rows.get(r?)?.get(c?).copied();
+                return [
+                    key(Some(r), col.checked_sub(1)),
+                    key(Some(r), Some(col + 1)),
+                    key(r.checked_sub(1), Some(col)),
+                    key(r.checked_sub(1), Some(col + 1)),
+                    key(Some(r + 1), col.checked_sub(1)),
+                    key(Some(r + 1), Some(col)),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+            }
+        }
+        Vec::new()
+    }
+}
+
+/// Generate typo variants of remembered passphrases, most likely first.
+///
+/// The variants of all passphrases are generated step by step: the passphrases themselves,
+/// caps-lock inversion, swapped adjacent characters, deleted characters, doubled characters,
+/// adjacent-key slips, adjacent keys pressed in addition, a single character with the wrong case,
+/// and finally any printable ASCII character inserted or substituted anywhere.
+/// With `depth` > 1, the variants are varied again (without the last, generic step).
+/// Duplicates are removed.
+pub fn typo_variants(passphrases: &[&str], layout: &KeyboardLayout, depth: usize) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut variants = Vec::new();
+    let mut level: Vec<Vec<char>> = passphrases.iter().map(|p| p.chars().collect()).collect();
+    for p in &level {
+        let s: String = p.iter().collect();
+        if seen.insert(s.clone()) {
+            variants.push(s);
+        }
+    }
+    for d in 0..depth {
+        let mut next = Vec::new();
+        for step in 0..STEPS {
+            // the generic step is only done for single typos, it would be too much otherwise
+            if d > 0 && step == STEPS - 1 {
+                continue;
+            }
+            for p in &level {
+                for v in edits(p, layout, step) {
+                    let s: String = v.iter().collect();
+                    if seen.insert(s.clone()) {
+                        variants.push(s);
+                        next.push(v);
+                    }
+                }
+            }
+        }
+        level = next;
+    }
+    variants
+}
+
+/// Number of edit steps in `edits`
+const STEPS: usize = 8;
+
+/// The variants of `p` for one edit step, in the order of `typo_variants`
+fn edits(p: &[char], layout: &KeyboardLayout, step: usize) -> Vec<Vec<char>> {
+    let n = p.len();
+    let mut out = Vec::new();
+    let replaced = |i: usize, c: char| {
+        let mut v = p.to_vec();
+        v[i] = c;
+        v
+    };
+    let inserted = |i: usize, c: char| {
+        let mut v = p.to_vec();
+        v.insert(i, c);
+        v
+    };
+    match step {
+        0 => out.push(p.iter().map(|&c| toggle_case(c)).collect()),
+        1 => {
+            for i in 1..n {
+                let mut v = p.to_vec();
+                v.swap(i - 1, i);
+                out.push(v);
+            }
+        }
+        2 => {
+            for i in 0..n {
+                let mut v = p.to_vec();
+                v.remove(i);
+                out.push(v);
+            }
+        }
+        3 => {
+            for (i, &c) in p.iter().enumerate() {
+                out.push(inserted(i, c));
+            }
+        }
+        4 => {
+            for (i, &c) in p.iter().enumerate() {
+                for k in layout.neighbours(c) {
+                    out.push(replaced(i, k));
+                }
+            }
+        }
+        5 => {
+            for (i, &c) in p.iter().enumerate() {
+                for k in layout.neighbours(c) {
+                    out.push(inserted(i, k));
+                    out.push(inserted(i + 1, k));
+                }
+            }
+        }
+        6 => {
+            for (i, &c) in p.iter().enumerate() {
+                let toggled = toggle_case(c);
+                if toggled != c {
+                    out.push(replaced(i, toggled));
+                }
+            }
+        }
+        _ => {
+            for i in 0..=n {
+                for c in (b' '..=b'~').map(char::from) {
+                    out.push(inserted(i, c));
+                }
+            }
+            for i in 0..n {
+                for c in (b' '..=b'~').map(char::from) {
+                    out.push(replaced(i, c));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Switch between upper and lower case, for characters with a single-character counterpart
+fn toggle_case(c: char) -> char {
+    let other: Vec<char> = match c.is_lowercase() {
+        true => c.to_uppercase().collect(),
+        false => c.to_lowercase().collect(),
+    };
+    match other[..] {
+        [o] => o,
+        _ => c,
+    }
+}
@@ -0,0 +1,216 @@
+use crate::{
+    error::Error,
+    extract::{dearmor, is_armored},
+    hash::{PgpHash, Usage},
+    verify::{decrypt, derive_key},
+};
+use chrono::{DateTime, TimeZone, Utc};
+use digest::Digest;
+use pgp::{
+    packet::{LiteralData, Packet, PacketParser},
+    ser::Serialize,
+    Edata,
+};
+use std::io::{Cursor, Read};
+
+/// Nested compressed data packets deeper than this are rejected
+const MAX_NESTING: usize = 8;
+
+/// A passphrase-encrypted message (e.g. `gpg -c` or a PGP Zip archive), decrypted
+#[derive(Clone, Debug)]
+pub struct Message {
+    /// The files of the literal data packets, usually one
+    pub literals: Vec<Literal>,
+    /// Whether the message had a modification detection code (it was verified).
+    /// Messages without one may have been modified.
+    pub integrity_protected: bool,
+}
+
+/// The content of a literal data packet
+#[derive(Clone, Debug)]
+pub struct Literal {
+    /// `b` for binary, `t` for text, `u` for UTF-8 text
+    pub format: u8,
+    /// The original file name, may be empty or `_CONSOLE` if the data didn't come from a file
+    pub file_name: Vec<u8>,
+    pub created: DateTime<Utc>,
+    pub data: Vec<u8>,
+}
+
+/// Decrypt a passphrase-encrypted message, armored or binary.
+///
+/// The passphrase is tried against every SKESK packet, as in [`PgpHash::verify`]. SKESK packets
+/// that can't be verified (e.g. with an unsupported string-to-key) are skipped, an error is
+/// only returned if none is left. The whole message is then decrypted, its modification
+/// detection code (if any) is checked, and compressed data packets are decompressed (BZip2 is
+/// not supported).
+pub fn decrypt_message(data: &[u8], passphrase: &[u8]) -> Result<Message, Error> {
+    let data = match is_armored(data) {
+        true => dearmor(data)?,
+        false => data.to_vec(),
+    };
+    let mut session_keys = Vec::new();
+    let mut edata = None;
+    for packet in PacketParser::new(Cursor::new(data)) {
+        match packet? {
+            Packet::SymKeyEncryptedSessionKey(esk) => session_keys.push(esk),
+            Packet::SymEncryptedData(d) => edata = Some(Edata::SymEncryptedData(d)),
+            Packet::SymEncryptedProtectedData(d) => {
+                edata = Some(Edata::SymEncryptedProtectedData(d))
+            }
+            _ => continue,
+        }
+        if edata.is_some() {
+            break;
+        }
+    }
+    let edata = edata.ok_or_else(|| Error::InvalidMessage("no encrypted data".into()))?;
+    if session_keys.is_empty() {
+        return Err(Error::InvalidMessage(
+            "no passphrase-encrypted session key".into(),
+        ));
+    }
+
+    let mut hash = None;
+    // the error of the last session key that can't be used, reported if none can
+    let mut unusable = None;
+    let mut usable = 0;
+    for esk in &session_keys {
+        let candidate = match PgpHash::try_from((esk, &edata)) {
+            Ok(candidate) => candidate,
+            Err(e) => {
+                log::warn!("skipping a passphrase-encrypted session key: {e}");
+                unusable = Some(e);
+                continue;
+            }
+        };
+        // only the quick check bytes of integrity protected data: the MDC is checked below, so a
+        // modified message is not reported as a wrong passphrase
+        let matches = match candidate.usage {
+            Usage::Eighteen => quick_check(&candidate, passphrase),
+            _ => candidate.verify_bytes(passphrase),
+        };
+        let matches = match matches {
+            Ok(matches) => matches,
+            Err(e) => {
+                log::warn!("skipping a passphrase-encrypted session key: {e}");
+                unusable = Some(e);
+                continue;
+            }
+        };
+        usable += 1;
+        if matches {
+            hash = Some(candidate);
+            break;
+        }
+    }
+    let hash = match (hash, unusable) {
+        (Some(hash), _) => hash,
+        (None, Some(e)) if usable == 0 => return Err(e),
+        (None, _) => return Err(Error::WrongPassphrase("the message".into())),
+    };
+
+    // the hash may be truncated, decrypt all data
+    let bs = hash.cipher_algorithm.block_size().unwrap();
+    let key = derive_key(&hash, passphrase)?;
+    let mut plaintext = edata.data().to_vec();
+    let resync = hash.usage == Usage::Nine;
+    decrypt(
+        hash.cipher_algorithm,
+        &key,
+        &vec![0; bs],
+        &mut plaintext,
+        resync,
+    )?;
+
+    let integrity_protected = hash.usage == Usage::Eighteen;
+    let content = match integrity_protected {
+        // the MDC packet (0xd3, length 20) hashes the prefix, the packets and its own header
+        true => {
+            let Some(split) = plaintext.len().checked_sub(20).filter(|&i| i >= bs + 4) else {
+                return Err(Error::Integrity("no modification detection code".into()));
+            };
+            let (hashed, mdc) = plaintext.split_at(split);
+            if !hashed.ends_with(&[0xd3, 0x14]) || sha1::Sha1::digest(hashed)[..] != *mdc {
+                return Err(Error::Integrity(
+                    "the message has been modified or is incomplete".into(),
+                ));
+            }
+            &hashed[bs + 2..hashed.len() - 2]
+        }
+        false => &plaintext[bs + 2..],
+    };
+
+    let mut literals = Vec::new();
+    read_packets(content, &mut literals, 0)?;
+    Ok(Message {
+        literals,
+        integrity_protected,
+    })
+}
+
+/// Whether the quick check bytes after the random prefix match with the key derived from
+/// `passphrase`
+fn quick_check(hash: &PgpHash, passphrase: &[u8]) -> Result<bool, Error> {
+    let bs = hash
+        .cipher_algorithm
+        .block_size()
+        .ok_or_else(|| Error::UnsupportedAlgorithm("unknown cipher".into()))?;
+    if hash.data.len() < bs + 2 {
+        return Err(Error::HashParse("encrypted data is too short".into()));
+    }
+    let key = derive_key(hash, passphrase)?;
+    let mut prefix = hash.data[..bs + 2].to_vec();
+    decrypt(
+        hash.cipher_algorithm,
+        &key,
+        &vec![0; bs],
+        &mut prefix,
+        false,
+    )?;
+    Ok(prefix[bs - 2..bs] == prefix[bs..])
+}
+
+/// Collect the literal data packets of decrypted data, decompressing compressed data
+fn read_packets(data: &[u8], literals: &mut Vec<Literal>, depth: usize) -> Result<(), Error> {
+    if depth > MAX_NESTING {
+        return Err(Error::InvalidMessage(
+            "too deeply nested compressed data".into(),
+        ));
+    }
+    for packet in PacketParser::new(Cursor::new(data)) {
+        match packet? {
+            Packet::LiteralData(literal) => literals.push(Literal::try_from(&literal)?),
+            Packet::CompressedData(compressed) => {
+                let mut decompressed = Vec::new();
+                compressed.decompress()?.read_to_end(&mut decompressed)?;
+                read_packets(&decompressed, literals, depth + 1)?;
+            }
+            Packet::OnePassSignature(_) | Packet::Signature(_) => {
+                log::info!("skipping a signature of the message, it is not verified")
+            }
+            p => log::warn!("skipping unexpected {:?} packet", p.tag()),
+        }
+    }
+    Ok(())
+}
+
+impl TryFrom<&LiteralData> for Literal {
+    type Error = Error;
+
+    /// The `pgp` crate doesn't expose the fields, they are read from the serialized packet:
+    /// format, length of the file name, file name, date and data (RFC4880, Section 5.9).
+    fn try_from(literal: &LiteralData) -> Result<Self, Self::Error> {
+        let body = literal.to_bytes()?;
+        let name_len = usize::from(body[1]);
+        let (name, rest) = body[2..].split_at(name_len);
+        let (date, data) = rest.split_at(4);
+        let date = u32::from_be_bytes(date.try_into().unwrap());
+        Ok(Literal {
+            format: body[0],
+            file_name: name.to_vec(),
+            created: Utc.timestamp_opt(date.into(), 0).unwrap(),
+            data: data.to_vec(),
+        })
+    }
+}
@@ -0,0 +1,277 @@
+use crate::{
+    hash::{Algorithm, CipherAlgorithm, ExtraData, HashAlgorithm, PgpHash, StringToKey, Usage},
+    protect::{coded_count, decode_count},
+};
+use std::fmt::{self, Display};
+
+/// A field of a hash with its value and what it means
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    /// The position of the field among the `*`-separated fields after `$gpg$`, starting at 1.
+    /// None for the extra data if it is absent.
+    pub index: Option<usize>,
+    /// The name of the field, as in [`PgpHash`]
+    pub name: &'static str,
+    /// The value as it appears in the hash, hex strings shortened
+    pub value: String,
+    pub meaning: String,
+}
+
+/// Every field of `hash` in the order of the `$gpg$` format, with its meaning. Fields that the
+/// format leaves out for this hash (the IV of messages, the count and salt of simple
+/// string-to-key functions) are skipped; the extra data is always listed. The meaning of the
+/// count depends on the `length` of the passphrase in bytes.
+pub fn describe(hash: &PgpHash, length: usize) -> Vec<Field> {
+    let mut fields = Vec::new();
+    let mut add = |name, value: String, meaning: String| {
+        let index = Some(fields.len() + 1);
+        fields.push(Field {
+            index,
+            name,
+            value,
+            meaning,
+        });
+    };
+
+    add(
+        "algorithm",
+        (hash.algorithm as i32).to_string(),
+        algorithm_name(hash.algorithm).into(),
+    );
+    add(
+        "data_len",
+        hash.data_len.to_string(),
+        format!("{} bytes of encrypted data", hash.data.len()),
+    );
+    if let Some(bits) = hash.bits {
+        add("bits", bits.to_string(), format!("{bits}-bit public key"));
+    }
+    add(
+        "data",
+        short_hex(&hash.data),
+        match hash.algorithm {
+            Algorithm::Symmetric => "start of the encrypted message".into(),
+            _ => "encrypted secret key material".into(),
+        },
+    );
+    add(
+        "s2k",
+        (hash.s2k as i32).to_string(),
+        match hash.s2k {
+            StringToKey::Simple => "simple string-to-key: the passphrase is hashed once".into(),
+            StringToKey::Salted => {
+                "salted string-to-key: salt and passphrase are hashed once".into()
+            }
+            StringToKey::IteratedSalted => {
+                "iterated and salted string-to-key: salt and passphrase are hashed repeatedly"
+                    .into()
+            }
+        },
+    );
+    add(
+        "usage",
+        (hash.usage as i32).to_string(),
+        usage_meaning(hash.usage).into(),
+    );
+    add(
+        "hash_algorithm",
+        (hash.hash_algorithm as i32).to_string(),
+        match hash.hash_algorithm.digest_size() {
+            Some(size) => format!("{:?}, {size}-byte digest", hash.hash_algorithm),
+            None => "unknown digest".into(),
+        },
+    );
+    add(
+        "cipher_algorithm",
+        (hash.cipher_algorithm as i32).to_string(),
+        match (
+            hash.cipher_algorithm.key_size(),
+            hash.cipher_algorithm.block_size(),
+        ) {
+            (Some(key), Some(block)) => format!(
+                "{:?}, {}-bit key, {block}-byte blocks",
+                hash.cipher_algorithm,
+                key * 8
+            ),
+            _ => "unknown cipher".into(),
+        },
+    );
+    if let Some(iv_len) = hash.iv_len {
+        add(
+            "iv_len",
+            iv_len.to_string(),
+            format!("{iv_len}-byte initialization vector"),
+        );
+    }
+    if let Some(iv) = &hash.iv {
+        add(
+            "iv",
+            short_hex(iv),
+            "initialization vector of the CFB mode".into(),
+        );
+    }
+    if let Some(count) = hash.count {
+        let meaning = count_meaning(hash.s2k, count, length);
+        add("count", count.to_string(), meaning);
+    }
+    if let Some(salt) = hash.salt {
+        add(
+            "salt",
+            hex::encode(salt),
+            "8-byte salt, hashed before the passphrase".into(),
+        );
+    }
+    let index = Some(fields.len() + 1);
+    fields.push(match &hash.extra_data {
+        None => Field {
+            index: None,
+            name: "extra_data",
+            value: "-".into(),
+            meaning: match hash.algorithm {
+                Algorithm::Symmetric => "absent, the message is checked by its contents".into(),
+                _ => "absent, a wrong passphrase is only ruled out by the checksum".into(),
+            },
+        },
+        Some(extra) => {
+            let (value, parts) = match extra {
+                ExtraData::Rsa { p } => ("RSA", vec![("n", p)]),
+                ExtraData::Dsa { p, q, g, y } => {
+                    ("DSA", vec![("p", p), ("q", q), ("g", g), ("y", y)])
+                }
+                ExtraData::ElGamal { p, g, y } => ("ElGamal", vec![("p", p), ("g", g), ("y", y)]),
+            };
+            let parts: Vec<_> = parts
+                .iter()
+                .map(|(name, v)| format!("{name} ({} bytes)", v.len()))
+                .collect();
+            Field {
+                index,
+                name: "extra_data",
+                value: value.into(),
+                meaning: format!(
+                    "public key {}, to check the decrypted key against",
+                    parts.join(", ")
+                ),
+            }
+        }
+    });
+    fields
+}
+
+fn algorithm_name(algorithm: Algorithm) -> &'static str {
+    match algorithm {
+        Algorithm::Symmetric => "passphrase-encrypted message (0 is specific to the hash format)",
+        Algorithm::RSAEncSign => "RSA (encrypt or sign) secret key",
+        Algorithm::RSAEncOnly => "RSA (encrypt only) secret key",
+        Algorithm::RsaSignOnly => "RSA (sign only) secret key",
+        Algorithm::ElGamal => "ElGamal (encrypt only) secret key",
+        Algorithm::DSA => "DSA secret key",
+        Algorithm::EC => "ECDH secret key",
+        Algorithm::ECDSA => "ECDSA secret key",
+        Algorithm::ElGamalEncSign => "ElGamal (encrypt or sign, reserved) secret key",
+        Algorithm::DH => "Diffie-Hellman (X9.42, reserved) secret key",
+    }
+}
+
+fn usage_meaning(usage: Usage) -> &'static str {
+    match usage {
+        Usage::Zero => "legacy protection of the key, checked by a 2-byte checksum",
+        Usage::Nine => "encrypted data packet without integrity protection, OpenPGP CFB",
+        Usage::Eighteen => "integrity protected data packet, checked by its SHA-1 MDC",
+        Usage::TwoFiveFour => "protected key, checked by the SHA-1 hash of the key material",
+        Usage::TwoFiveFive => "protected key, checked by a 2-byte checksum",
+    }
+}
+
+/// The bytes hashed per digest context, the coded count and the repetitions of the salt and a
+/// passphrase of `length` bytes, which are hashed at least once even if the count is lower
+fn count_meaning(s2k: StringToKey, count: usize, length: usize) -> String {
+    if s2k != StringToKey::IteratedSalted {
+        return "unused by this string-to-key function".into();
+    }
+    let coded = coded_count(count);
+    let coded = match decode_count(coded) == count {
+        true => format!("coded {coded}"),
+        false => "not a coded count".into(),
+    };
+    format!(
+        "{count} bytes hashed ({coded}), the salt and a {length}-byte passphrase {} times",
+        (count / (8 + length)).max(1)
+    )
+}
+
+/// The first 16 bytes of `data` in hex, followed by `...` if there are more
+fn short_hex(data: &[u8]) -> String {
+    match data.len() > 16 {
+        true => format!("{}...", hex::encode(&data[..16])),
+        false => hex::encode(data),
+    }
+}
+
+/// A cracker, and the format or mode in which it attacks a hash
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cracker {
+    /// The built-in cracker, `pgp2hc crack`
+    Pgp2hc,
+    /// John the Ripper (jumbo) with this format
+    John(&'static str),
+    /// hashcat with this hash mode
+    Hashcat(u32),
+}
+
+impl Display for Cracker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Cracker::Pgp2hc => write!(f, "pgp2hc crack"),
+            Cracker::John(format) => write!(f, "john --format={format}"),
+            Cracker::Hashcat(mode) => write!(f, "hashcat -m {mode}"),
+        }
+    }
+}
+
+/// The crackers that can attack `hash`, as of John the Ripper 1.9.0-jumbo-1 and hashcat 6.2.6.
+///
+/// John's CPU format supports every algorithm of the hash format, its OpenCL format only
+/// iterated and salted string-to-key functions with SHA-1, SHA-256 or SHA-512. hashcat only
+/// supports secret keys (no ECC) protected by the SHA-1 hash of the key material with an
+/// iterated and salted string-to-key function, and only some combinations of digest and cipher.
+pub fn crackers(hash: &PgpHash) -> Vec<Cracker> {
+    let mut crackers = Vec::new();
+    if hash.verifier().is_ok() {
+        crackers.push(Cracker::Pgp2hc);
+    }
+    let known = hash.hash_algorithm != HashAlgorithm::Unknown
+        && hash.cipher_algorithm != CipherAlgorithm::Unknown;
+    if !known {
+        return crackers;
+    }
+    crackers.push(Cracker::John("gpg"));
+    let iterated = hash.s2k == StringToKey::IteratedSalted;
+    if iterated
+        && matches!(
+            hash.hash_algorithm,
+            HashAlgorithm::SHA1 | HashAlgorithm::SHA256 | HashAlgorithm::SHA512
+        )
+    {
+        crackers.push(Cracker::John("gpg-opencl"));
+    }
+    let hashcat_key = !matches!(
+        hash.algorithm,
+        Algorithm::Symmetric | Algorithm::EC | Algorithm::ECDSA
+    ) && hash.usage == Usage::TwoFiveFour;
+    let aes = matches!(
+        hash.cipher_algorithm,
+        CipherAlgorithm::AES128 | CipherAlgorithm::AES256
+    );
+    let mode = match (hash.hash_algorithm, hash.cipher_algorithm) {
+        (HashAlgorithm::SHA1, _) if aes => Some(17010),
+        (HashAlgorithm::SHA512, _) if aes => Some(17020),
+        (HashAlgorithm::SHA256, _) if aes => Some(17030),
+        (HashAlgorithm::SHA1, CipherAlgorithm::CAST5) => Some(17040),
+        _ => None,
+    };
+    if let (true, true, Some(mode)) = (iterated, hashcat_key, mode) {
+        crackers.push(Cracker::Hashcat(mode));
+    }
+    crackers
+}
@@ -0,0 +1,117 @@
+use std::fmt::Display;
+use unicode_normalization::UnicodeNormalization;
+
+/// How a passphrase is turned into the bytes that are hashed.
+///
+/// Modern implementations hash the UTF-8 bytes of the passphrase as it was typed. Old PGP and
+/// GnuPG versions and Windows programs sometimes used Latin-1 or CP1252 instead, and the same
+/// text can be typed in different Unicode normalisation forms on different systems.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Encoding {
+    /// The bytes as given
+    Utf8,
+    /// UTF-8 in normalisation form C (composed characters)
+    Nfc,
+    /// UTF-8 in normalisation form D (decomposed characters)
+    Nfd,
+    /// ISO 8859-1, of the NFC form
+    Latin1,
+    /// Windows-1252, of the NFC form
+    Cp1252,
+}
+
+/// Characters of Windows-1252 at 0x80 to 0x9f, where it differs from Latin-1
+const CP1252_HIGH: [Option<char>; 32] = [
+    Some('€'),
+    None,
+    Some('‚'),
+    Some('ƒ'),
+    Some('„'),
+    Some('…'),
+    Some('†'),
+    Some('‡'),
+    Some('ˆ'),
+    Some('‰'),
+    Some('Š'),
+    Some('‹'),
+    Some('Œ'),
+    None,
+    Some('Ž'),
+    None,
+    None,
+    Some('‘'),
+    Some('’'),
+    Some('“'),
+    Some('”'),
+    Some('•'),
+    Some('–'),
+    Some('—'),
+    Some('˜'),
+    Some('™'),
+    Some('š'),
+    Some('›'),
+    Some('œ'),
+    None,
+    Some('ž'),
+    Some('Ÿ'),
+];
+
+impl Encoding {
+    pub const ALL: [Encoding; 5] = [
+        Encoding::Utf8,
+        Encoding::Nfc,
+        Encoding::Nfd,
+        Encoding::Latin1,
+        Encoding::Cp1252,
+    ];
+
+    /// Encode `passphrase`, None if it is not valid UTF-8 (except for `Utf8`, which keeps
+    /// the bytes as they are) or contains characters the encoding can't represent.
+    pub fn encode(&self, passphrase: &[u8]) -> Option<Vec<u8>> {
+        let text = || std::str::from_utf8(passphrase).ok();
+        match self {
+            Encoding::Utf8 => Some(passphrase.to_vec()),
+            Encoding::Nfc => Some(text()?.nfc().collect::<String>().into_bytes()),
+            Encoding::Nfd => Some(text()?.nfd().collect::<String>().into_bytes()),
+            Encoding::Latin1 => text()?.nfc().map(|c| u8::try_from(c).ok()).collect(),
+            Encoding::Cp1252 => text()?
+                .nfc()
+                .map(|c| match u8::try_from(c) {
+                    Ok(b) if !(0x80..0xa0).contains(&b) => Some(b),
+                    _ => CP1252_HIGH
+                        .iter()
+                        .position(|&h| h == Some(c))
+                        .map(|i| 0x80 + i as u8),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Nfc => "UTF-8 (NFC)",
+            Encoding::Nfd => "UTF-8 (NFD)",
+            Encoding::Latin1 => "Latin-1",
+            Encoding::Cp1252 => "CP1252",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// The distinct byte strings of `passphrase` in the given encodings, with the first encoding
+/// that produced each of them. An ASCII passphrase therefore yields a single variant.
+pub fn encode_variants(passphrase: &[u8], encodings: &[Encoding]) -> Vec<(Encoding, Vec<u8>)> {
+    let mut variants: Vec<(Encoding, Vec<u8>)> = Vec::new();
+    for e in encodings {
+        if let Some(bytes) = e.encode(passphrase) {
+            if !variants.iter().any(|(_, b)| *b == bytes) {
+                variants.push((*e, bytes));
+            }
+        }
+    }
+    variants
+}
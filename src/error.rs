@@ -0,0 +1,87 @@
+use thiserror::Error;
+
+/// All errors that can occur in this crate
+#[derive(Debug, Error)]
+pub enum Error {
+    /// A file or stream could not be read or written
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// The ASCII armor of the input could not be removed
+    #[error("invalid ASCII armor: {0}")]
+    Armor(std::io::Error),
+    /// A packet could not be parsed by the `pgp` crate
+    #[error("invalid packet: {0}")]
+    Packet(#[from] pgp::errors::Error),
+    /// The secret key material is not encrypted, so there is no hash to extract
+    #[error("secret key is not protected by a passphrase")]
+    Unprotected,
+    /// A stub of a secret key without its secret key material, e.g. for a key on a smartcard
+    #[error("{0} has no secret key material (it is offline or on a smartcard)")]
+    NoSecretKeyMaterial(String),
+    /// A public key, cipher or hash algorithm that can't be represented in the hash format
+    #[error("unsupported algorithm: {0}")]
+    UnsupportedAlgorithm(String),
+    /// A string-to-key specifier or usage that can't be represented in the hash format
+    #[error("unsupported string-to-key: {0}")]
+    UnsupportedS2k(String),
+    /// The input holds nothing to work on, e.g. no hashes or no user IDs
+    #[error("nothing to do: {0}")]
+    EmptyInput(String),
+    /// Command-line arguments contradict each other in a way clap can't express
+    #[error("invalid arguments: {0}")]
+    InvalidArguments(String),
+    /// A hash in the john/hashcat format could not be parsed
+    #[error("invalid hash: {0}")]
+    HashParse(String),
+    /// A mask for the built-in cracker could not be parsed
+    #[error("invalid mask: {0}")]
+    InvalidMask(String),
+    /// A rule for the built-in cracker could not be parsed
+    #[error("invalid rule: {0}")]
+    InvalidRule(String),
+    /// A rule uses a function of hashcat that the built-in cracker doesn't implement
+    #[error("unsupported rule: {0}")]
+    UnsupportedRule(String),
+    /// A keyboard layout for typo variants could not be parsed
+    #[error("invalid keyboard layout: {0}")]
+    InvalidLayout(String),
+    /// A part of the keyspace to test can't be determined
+    #[error("invalid keyspace slice: {0}")]
+    InvalidSlice(String),
+    /// A session file of the built-in cracker could not be read or does not fit the attack
+    #[error("invalid session: {0}")]
+    InvalidSession(String),
+    /// An audit policy could not be read
+    #[error("invalid policy: {0}")]
+    InvalidPolicy(String),
+    /// A calibration file with the speeds of this computer could not be read
+    #[error("invalid calibration: {0}")]
+    InvalidCalibration(String),
+    /// A passphrase-encrypted message lacks packets or is nested too deeply
+    #[error("invalid message: {0}")]
+    InvalidMessage(String),
+    /// The passphrase doesn't unlock a secret key or message
+    #[error("wrong passphrase for {0}")]
+    WrongPassphrase(String),
+    /// A passphrase can't be used, e.g. because it is not valid UTF-8
+    #[error("invalid passphrase: {0}")]
+    InvalidPassphrase(String),
+    /// No passphrase is known for a secret key or message
+    #[error("no passphrase for {0}")]
+    MissingPassphrase(String),
+    /// The modification detection code of a decrypted message doesn't match
+    #[error("integrity check failed: {0}")]
+    Integrity(String),
+}
+
+impl From<std::num::ParseIntError> for Error {
+    fn from(e: std::num::ParseIntError) -> Self {
+        Error::HashParse(format!("invalid number: {e}"))
+    }
+}
+
+impl From<hex::FromHexError> for Error {
+    fn from(e: hex::FromHexError) -> Self {
+        Error::HashParse(format!("invalid hex string: {e}"))
+    }
+}
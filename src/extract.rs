@@ -0,0 +1,470 @@
+use crate::error::Error;
+use crate::hash::PgpHash;
+use crate::potfile::Potfile;
+use crate::UserInfo;
+use chrono::{DateTime, Utc};
+use pgp::{
+    armor::Dearmor,
+    packet::{Packet, PacketParser, SymKeyEncryptedSessionKey},
+    types::{KeyId, KeyTrait},
+    Edata, SignedSecretKey,
+};
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    io::{Cursor, Read},
+    path::{Path, PathBuf},
+};
+
+/// Whether the input is ASCII-armored
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DearmorMode {
+    /// Dearmor if the input starts with an armor header (`-----BEGIN PGP ...`)
+    #[default]
+    Auto,
+    /// The input is always armored
+    Always,
+    /// The input is binary OpenPGP data
+    Never,
+}
+
+/// Where a record or warning was found
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Source {
+    /// The file the data was read from, if it was read from a file
+    pub path: Option<PathBuf>,
+    /// Index of the packet in the (dearmored) OpenPGP data, starting at 0.
+    /// None if the key was not parsed from serialized data.
+    pub packet: Option<usize>,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.path, self.packet) {
+            (Some(p), Some(i)) => write!(f, "{}, packet {i}", p.display()),
+            (Some(p), None) => write!(f, "{}", p.display()),
+            (None, Some(i)) => write!(f, "packet {i}"),
+            (None, None) => write!(f, "<key>"),
+        }
+    }
+}
+
+/// A hash extracted from a key, with everything known about that key
+#[derive(Clone, Debug)]
+pub struct Record {
+    pub hash: PgpHash,
+    /// The first user ID of the certificate the key belongs to
+    pub user: Option<UserInfo>,
+    /// None if the hash does not belong to a key
+    pub key_id: Option<KeyId>,
+    /// None if the hash does not belong to a key
+    pub fingerprint: Option<Vec<u8>>,
+    /// The creation time of the key, None if the hash does not belong to a key
+    pub created: Option<DateTime<Utc>>,
+    pub is_subkey: bool,
+    pub source: Source,
+    /// The passphrase, if the hash is in the potfile given to the [`Extractor`]
+    pub password: Option<Vec<u8>>,
+}
+
+/// A problem that did not stop the extraction, e.g. a packet that could not be parsed
+#[derive(Debug)]
+pub struct Warning {
+    pub source: Source,
+    pub error: Error,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.source, self.error)
+    }
+}
+
+/// A single item produced by the [`Extractor`]
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)] // almost all items are records
+pub enum Extracted {
+    Record(Record),
+    Warning(Warning),
+}
+
+/// The relevant content of a single packet
+#[allow(clippy::large_enum_variant)] // almost all artefacts are hashes
+pub enum Artefact {
+    /// A hash, along with the key it was extracted from
+    Hash {
+        hash: PgpHash,
+        key_id: KeyId,
+        fingerprint: Vec<u8>,
+        created: DateTime<Utc>,
+        is_subkey: bool,
+    },
+    User(UserInfo),
+    /// The parameters for a passphrase-encrypted message, the data follows in a later packet
+    SessionKey(SymKeyEncryptedSessionKey),
+    EncryptedData(Edata),
+}
+
+type Filter = Box<dyn Fn(&Record) -> bool>;
+
+/// Extracts hashes from OpenPGP data, configured with builder-style methods.
+#[derive(Default)]
+pub struct Extractor {
+    no_subkeys: bool,
+    dearmor: DearmorMode,
+    filters: Vec<Filter>,
+    potfile: Option<Potfile>,
+}
+
+impl Extractor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to extract hashes from subkeys (default: true)
+    pub fn subkeys(mut self, subkeys: bool) -> Self {
+        self.no_subkeys = !subkeys;
+        self
+    }
+
+    /// How to handle ASCII armor (default: [`DearmorMode::Auto`])
+    pub fn dearmor(mut self, mode: DearmorMode) -> Self {
+        self.dearmor = mode;
+        self
+    }
+
+    /// Look up every hash in `potfile`, and set the passphrase of the records that were cracked.
+    /// This happens before the filters are applied.
+    pub fn potfile(mut self, potfile: Potfile) -> Self {
+        self.potfile = Some(potfile);
+        self
+    }
+
+    /// Only return records for which `filter` returns true.
+    /// Can be called multiple times, all filters must match.
+    pub fn filter(mut self, filter: impl Fn(&Record) -> bool + 'static) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Set the passphrase from the potfile and apply the filters
+    fn accept(&self, record: &mut Record) -> bool {
+        if let Some(potfile) = &self.potfile {
+            record.password = potfile.get(&record.hash).map(<[u8]>::to_vec);
+        }
+        self.filters.iter().all(|f| f(record))
+    }
+
+    /// Extract from the file at `path`. The path is recorded in the [`Source`] of each item.
+    pub fn extract_file(&self, path: impl AsRef<Path>) -> Result<Records<'_>, Error> {
+        let f = std::fs::File::open(path.as_ref())?;
+        let mut records = self.extract(f)?;
+        records.path = Some(path.as_ref().to_path_buf());
+        Ok(records)
+    }
+
+    pub fn extract_bytes(&self, data: &[u8]) -> Result<Records<'_>, Error> {
+        self.extract(data)
+    }
+
+    /// Extract from arbitrary input. The input is read into memory completely.
+    pub fn extract(&self, mut reader: impl Read) -> Result<Records<'_>, Error> {
+        let mut input = Vec::new();
+        reader.read_to_end(&mut input)?;
+        let armored = match self.dearmor {
+            DearmorMode::Always => true,
+            DearmorMode::Never => false,
+            DearmorMode::Auto => is_armored(&input),
+        };
+        let data = match armored {
+            true => dearmor(&input)?,
+            false => input,
+        };
+        log::info!("got {} bytes of OpenPGP data", data.len());
+        Ok(Records {
+            extractor: self,
+            packets: PacketParser::new(Cursor::new(data)).enumerate(),
+            path: None,
+            user: None,
+            pending: Vec::new(),
+            session_keys: Vec::new(),
+            ready: VecDeque::new(),
+        })
+    }
+
+    /// Extract from an already parsed key. All keys are bound to the key's first user ID.
+    pub fn extract_key(&self, key: &SignedSecretKey) -> Vec<Extracted> {
+        let user = key
+            .details
+            .users
+            .first()
+            .map(|u| UserInfo::from(u.id.id().to_string().as_str()));
+        let source = Source {
+            path: None,
+            packet: None,
+        };
+        let primary = (
+            PgpHash::try_from(&key.primary_key),
+            key.primary_key.key_id(),
+            key.primary_key.fingerprint(),
+            *key.primary_key.created_at(),
+            false,
+        );
+        let subkeys = key
+            .secret_subkeys
+            .iter()
+            .filter(|_| !self.no_subkeys)
+            .map(|k| {
+                (
+                    PgpHash::try_from(&k.key),
+                    k.key.key_id(),
+                    k.key.fingerprint(),
+                    *k.key.created_at(),
+                    true,
+                )
+            });
+        std::iter::once(primary)
+            .chain(subkeys)
+            .filter_map(
+                |(hash, key_id, fingerprint, created, is_subkey)| match hash {
+                    Ok(hash) => {
+                        let mut record = Record {
+                            hash,
+                            user: user.clone(),
+                            key_id: Some(key_id),
+                            fingerprint: Some(fingerprint),
+                            created: Some(created),
+                            is_subkey,
+                            source: source.clone(),
+                            password: None,
+                        };
+                        match self.accept(&mut record) {
+                            true => Some(Extracted::Record(record)),
+                            false => None,
+                        }
+                    }
+                    Err(error) => Some(Extracted::Warning(Warning {
+                        source: source.clone(),
+                        error,
+                    })),
+                },
+            )
+            .collect()
+    }
+}
+
+/// Iterator over the items extracted from a single input, see [`Extractor`].
+///
+/// Records are returned once the user ID of their certificate is known,
+/// i.e. a primary key is held back until the following User ID packet.
+pub struct Records<'a> {
+    extractor: &'a Extractor,
+    packets: std::iter::Enumerate<PacketParser<Cursor<Vec<u8>>>>,
+    path: Option<PathBuf>,
+    /// first user ID of the current certificate
+    user: Option<UserInfo>,
+    /// records of the current certificate that wait for a user ID
+    pending: Vec<Record>,
+    /// SKESK packets that wait for the encrypted data
+    session_keys: Vec<(usize, SymKeyEncryptedSessionKey)>,
+    ready: VecDeque<Extracted>,
+}
+
+impl Records<'_> {
+    fn source(&self, packet: usize) -> Source {
+        Source {
+            path: self.path.clone(),
+            packet: Some(packet),
+        }
+    }
+
+    fn push(&mut self, mut record: Record) {
+        if self.extractor.accept(&mut record) {
+            self.ready.push_back(Extracted::Record(record));
+        }
+    }
+
+    /// the current certificate ends, release everything still waiting for a user ID
+    fn flush(&mut self) {
+        for r in std::mem::take(&mut self.pending) {
+            self.push(r);
+        }
+        self.user = None;
+    }
+
+    fn handle(&mut self, index: usize, packet: Packet) {
+        let starts_certificate = matches!(packet, Packet::SecretKey(_) | Packet::PublicKey(_));
+        if starts_certificate {
+            self.flush();
+        }
+        match handle_packet(packet, self.extractor.no_subkeys) {
+            Ok(Some(Artefact::Hash {
+                hash,
+                key_id,
+                fingerprint,
+                created,
+                is_subkey,
+            })) => {
+                let record = Record {
+                    hash,
+                    user: self.user.clone(),
+                    key_id: Some(key_id),
+                    fingerprint: Some(fingerprint),
+                    created: Some(created),
+                    is_subkey,
+                    source: self.source(index),
+                    password: None,
+                };
+                match self.user {
+                    Some(_) => self.push(record),
+                    None => self.pending.push(record),
+                }
+            }
+            Ok(Some(Artefact::User(u))) => {
+                if self.user.is_none() {
+                    for mut r in std::mem::take(&mut self.pending) {
+                        r.user = Some(u.clone());
+                        self.push(r);
+                    }
+                    self.user = Some(u);
+                }
+            }
+            Ok(Some(Artefact::SessionKey(esk))) => self.session_keys.push((index, esk)),
+            Ok(Some(Artefact::EncryptedData(edata))) => {
+                // each SKESK gives a hash (one per passphrase that can decrypt the message)
+                for (i, esk) in std::mem::take(&mut self.session_keys) {
+                    let item = match PgpHash::try_from((&esk, &edata)) {
+                        Ok(hash) => Extracted::Record(Record {
+                            hash,
+                            user: None,
+                            key_id: None,
+                            fingerprint: None,
+                            created: None,
+                            is_subkey: false,
+                            source: self.source(i),
+                            password: None,
+                        }),
+                        Err(error) => Extracted::Warning(Warning {
+                            source: self.source(i),
+                            error,
+                        }),
+                    };
+                    match item {
+                        Extracted::Record(r) => self.push(r),
+                        w => self.ready.push_back(w),
+                    }
+                }
+            }
+            Ok(None) => (),
+            Err(error) => self.ready.push_back(Extracted::Warning(Warning {
+                source: self.source(index),
+                error,
+            })),
+        }
+    }
+}
+
+impl Iterator for Records<'_> {
+    type Item = Extracted;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.ready.pop_front() {
+                return Some(item);
+            }
+            match self.packets.next() {
+                Some((i, Ok(packet))) => self.handle(i, packet),
+                Some((i, Err(e))) => self.ready.push_back(Extracted::Warning(Warning {
+                    source: self.source(i),
+                    error: Error::Packet(e),
+                })),
+                None => {
+                    self.flush();
+                    if self.ready.is_empty() {
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Whether the input starts with an armor header (`-----BEGIN PGP ...`)
+pub(crate) fn is_armored(input: &[u8]) -> bool {
+    input.trim_ascii_start().starts_with(b"-----BEGIN PGP")
+}
+
+pub(crate) fn dearmor(input: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::new();
+    Dearmor::new(Cursor::new(input))
+        .read_to_end(&mut data)
+        .map_err(Error::Armor)?;
+    Ok(data)
+}
+
+pub fn handle_packet(packet: Packet, no_subkeys: bool) -> Result<Option<Artefact>, Error> {
+    Ok(match packet {
+        Packet::SecretKey(x) => {
+            log::info!("got a SecretKey packet");
+            let key_id = x.key_id();
+            let fingerprint = x.fingerprint();
+            Some(Artefact::Hash {
+                hash: PgpHash::try_from(&x)?,
+                key_id,
+                fingerprint,
+                created: *x.created_at(),
+                is_subkey: false,
+            })
+        }
+        Packet::SecretSubkey(x) => match no_subkeys {
+            true => {
+                log::info!("ignoring SecretSubkey packet because --no-subkeys was specified");
+                None
+            }
+            false => {
+                let key_id = x.key_id();
+                let fingerprint = x.fingerprint();
+                Some(Artefact::Hash {
+                    hash: PgpHash::try_from(&x)?,
+                    key_id,
+                    fingerprint,
+                    created: *x.created_at(),
+                    is_subkey: true,
+                })
+            }
+        },
+        Packet::Signature(_) => {
+            log::info!("ignoring Signature packet");
+            None
+        }
+        Packet::Trust(_) => {
+            log::info!("ignoring Trust packet");
+            None
+        }
+        Packet::UserId(x) => Some(Artefact::User(UserInfo::from(x.id().to_string().as_str()))),
+        Packet::UserAttribute(_) => {
+            log::info!("ignoring UserAttribute packet");
+            None
+        }
+        Packet::SymKeyEncryptedSessionKey(x) => {
+            log::info!("got a SymKeyEncryptedSessionKey packet");
+            Some(Artefact::SessionKey(x))
+        }
+        Packet::SymEncryptedData(x) => Some(Artefact::EncryptedData(Edata::SymEncryptedData(x))),
+        Packet::SymEncryptedProtectedData(x) => {
+            Some(Artefact::EncryptedData(Edata::SymEncryptedProtectedData(x)))
+        }
+        // Packet::LiteralData(_) => todo!(),
+        // Packet::Marker(_) => todo!(),
+        // Packet::ModDetectionCode(_) => todo!(),
+        // Packet::OnePassSignature(_) => todo!(),
+        // Packet::PublicKeyEncryptedSessionKey(_) => todo!(),
+        // Packet::CompressedData(_) => todo!(),
+        // Packet::PublicKey(_) => todo!(),
+        // Packet::PublicSubkey(_) => todo!(),
+        _ => {
+            log::info!("ignoring unhandled packet");
+            None
+        }
+    })
+}
@@ -1,9 +1,10 @@
+use crate::error::Error;
 use pgp::crypto::sym::SymmetricKeyAlgorithm;
 use std::convert::TryFrom;
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
 use strum_macros::FromRepr;
 
-#[derive(Clone, Copy, Debug, FromRepr, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, FromRepr, PartialEq, Eq, Hash)]
 #[repr(i32)]
 pub enum StringToKey {
     /// In this case no salt is used, so `count` and `salt` in the hash are None
@@ -14,7 +15,7 @@ pub enum StringToKey {
 
 /// According to RFC4880, Section 9.1. Public-Key Algorithms.
 /// 100 to 110 are reserved for Private/Experimental algorithms
-#[derive(Clone, Copy, Debug, FromRepr, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, FromRepr, PartialEq, Eq, Hash)]
 #[repr(i32)]
 pub enum Algorithm {
     /// The value 0 is not in the RFC, and is a john-specific addition
@@ -32,7 +33,7 @@ pub enum Algorithm {
     DH = 21,
 }
 
-#[derive(Clone, Copy, Debug, FromRepr)]
+#[derive(Clone, Copy, Debug, FromRepr, PartialEq, Eq, Hash)]
 #[repr(i32)]
 pub enum CipherAlgorithm {
     Unknown = -1,
@@ -49,8 +50,40 @@ pub enum CipherAlgorithm {
     Camellia256 = 13,
 }
 
+impl CipherAlgorithm {
+    /// Key size in bytes, None for `Unknown`
+    pub fn key_size(&self) -> Option<usize> {
+        match self {
+            CipherAlgorithm::Unknown => None,
+            CipherAlgorithm::IDEA
+            | CipherAlgorithm::CAST5
+            | CipherAlgorithm::Blowfish
+            | CipherAlgorithm::AES128
+            | CipherAlgorithm::Camellia128 => Some(16),
+            CipherAlgorithm::TripleDES | CipherAlgorithm::AES192 | CipherAlgorithm::Camellia192 => {
+                Some(24)
+            }
+            CipherAlgorithm::AES256 | CipherAlgorithm::Twofish | CipherAlgorithm::Camellia256 => {
+                Some(32)
+            }
+        }
+    }
+
+    /// Block size in bytes, None for `Unknown`
+    pub fn block_size(&self) -> Option<usize> {
+        match self {
+            CipherAlgorithm::Unknown => None,
+            CipherAlgorithm::IDEA
+            | CipherAlgorithm::TripleDES
+            | CipherAlgorithm::CAST5
+            | CipherAlgorithm::Blowfish => Some(8),
+            _ => Some(16),
+        }
+    }
+}
+
 impl TryFrom<SymmetricKeyAlgorithm> for CipherAlgorithm {
-    type Error = String;
+    type Error = Error;
 
     fn try_from(value: SymmetricKeyAlgorithm) -> Result<Self, Self::Error> {
         match value {
@@ -67,12 +100,12 @@ impl TryFrom<SymmetricKeyAlgorithm> for CipherAlgorithm {
             SymmetricKeyAlgorithm::Camellia256 => Ok(CipherAlgorithm::Camellia256),
             // SymmetricKeyAlgorithm::Plaintext => todo!(),
             // SymmetricKeyAlgorithm::Private10 => CipherAlgorithm::Private10,
-            _ => Err("unknown CipherAlgorithm found".into()),
+            a => Err(Error::UnsupportedAlgorithm(format!("cipher {a:?}"))),
         }
     }
 }
 
-#[derive(Clone, Copy, Debug, FromRepr)]
+#[derive(Clone, Copy, Debug, FromRepr, PartialEq, Eq, Hash)]
 #[repr(i32)]
 pub enum HashAlgorithm {
     Unknown = -1,
@@ -85,8 +118,32 @@ pub enum HashAlgorithm {
     SHA224 = 11,
 }
 
+impl HashAlgorithm {
+    /// Output size in bytes, None for `Unknown`
+    pub fn digest_size(&self) -> Option<usize> {
+        match self {
+            HashAlgorithm::Unknown => None,
+            HashAlgorithm::MD5 => Some(16),
+            HashAlgorithm::SHA1 | HashAlgorithm::RIPEMD160 => Some(20),
+            HashAlgorithm::SHA224 => Some(28),
+            HashAlgorithm::SHA256 => Some(32),
+            HashAlgorithm::SHA384 => Some(48),
+            HashAlgorithm::SHA512 => Some(64),
+        }
+    }
+
+    /// Size of the blocks processed by the compression function in bytes, None for `Unknown`
+    pub fn block_size(&self) -> Option<usize> {
+        match self {
+            HashAlgorithm::Unknown => None,
+            HashAlgorithm::SHA384 | HashAlgorithm::SHA512 => Some(128),
+            _ => Some(64),
+        }
+    }
+}
+
 /// The string-to-key ID
-#[derive(Clone, Copy, Debug, FromRepr, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, FromRepr, PartialEq, Eq, Hash)]
 #[repr(i32)]
 pub enum Usage {
     Zero = 0,
@@ -96,7 +153,46 @@ pub enum Usage {
     TwoFiveFive = 255,
 }
 
-#[derive(Debug)]
+/// The enums of the hash format that can be named in policies, calibration files and options
+pub trait Named: Copy + Debug {
+    fn from_id(id: i32) -> Option<Self>;
+
+    /// All variants, ordered by their IDs
+    fn all() -> Vec<Self> {
+        (-1..=255).filter_map(Self::from_id).collect()
+    }
+
+    /// The name of the variant in lower case, e.g. `sha256` or `tripledes`
+    fn name(&self) -> String {
+        format!("{self:?}").to_lowercase()
+    }
+
+    /// The variant with this name, ignoring case, `-` and `_`. `3des` is accepted for
+    /// `TripleDES`.
+    fn from_name(name: &str) -> Option<Self> {
+        let normalize = |s: &str| match s.replace(['-', '_'], "").to_lowercase() {
+            s if s == "3des" => "tripledes".to_string(),
+            s => s,
+        };
+        Self::all()
+            .into_iter()
+            .find(|t| normalize(&t.name()) == normalize(name))
+    }
+}
+
+macro_rules! impl_named {
+    ($($name:ty),*) => {
+        $(impl Named for $name {
+            fn from_id(id: i32) -> Option<Self> {
+                <$name>::from_repr(id)
+            }
+        })*
+    };
+}
+
+impl_named!(StringToKey, HashAlgorithm, CipherAlgorithm, Usage);
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ExtraData {
     /// DSA: p, q, g, y
     Dsa {
@@ -112,6 +208,10 @@ pub enum ExtraData {
 }
 
 /// A hash for the OpenPGP format, prefixed with `$gpg$`.
+///
+/// Two hashes compare equal if all protection parameters (S2K, salt, count, cipher, IV)
+/// and the encrypted data are identical, i.e. if they were extracted from the same key.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct PgpHash {
     pub algorithm: Algorithm,
     pub data_len: usize,
@@ -161,7 +261,7 @@ impl Display for PgpHash {
             write!(f, "*{}", c)?;
         }
         if let Some(s) = self.salt {
-            write!(f, "*{}", hex::encode(&s))?;
+            write!(f, "*{}", hex::encode(s))?;
         }
         // extra data, if available
         if let Some(extra) = &self.extra_data {
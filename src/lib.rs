@@ -1,21 +1,77 @@
+/// Auditing the protection of secret keys against a policy.
+#[cfg(feature = "audit")]
+pub mod audit;
+/// Measuring the speed of the native string-to-key function and ciphers.
+#[cfg(feature = "cost")]
+pub mod benchmark;
+/// The command line interface of the `pgp2hc` binary.
+#[cfg(feature = "cli")]
+pub mod cli;
 /// Conversion of `pgp` data to a `PgpHash` structure.
 mod convert;
+/// Estimates of the work to test a candidate passphrase against a hash.
+#[cfg(feature = "cost")]
+pub mod cost;
+/// The built-in multi-threaded cracker.
+#[cfg(feature = "crack")]
+pub mod crack;
+/// Decryption of passphrase-encrypted messages.
+#[cfg(feature = "decrypt")]
+pub mod decrypt;
+/// Human-readable descriptions of the fields of a hash and the crackers that support it.
+#[cfg(feature = "describe")]
+pub mod describe;
+/// Encodings and normalisation forms of passphrases.
+#[cfg(feature = "encoding")]
+pub mod encoding;
+/// The error type of this crate.
+pub mod error;
+/// Extraction of hashes and related information from OpenPGP data.
+pub mod extract;
 /// Definition of a `PgpHash` data structure and related enums.
 pub mod hash;
 /// Functions related to parsing hashes in the john/hashcat format to the data structures defined in this crate
 mod parse;
+/// Personalised candidate wordlists from key metadata and user hints.
+#[cfg(feature = "personal")]
+pub mod personal;
+/// Reading and writing john and hashcat potfiles.
+pub mod potfile;
+/// Re-protecting secret keys with a new passphrase and string-to-key parameters.
+#[cfg(feature = "protect")]
+pub mod protect;
+/// A fast iterated and salted string-to-key function.
+mod s2k;
 #[cfg(test)]
+#[allow(
+    clippy::collapsible_if,
+    clippy::into_iter_on_ref,
+    clippy::needless_borrow,
+    clippy::redundant_static_lifetimes,
+    clippy::while_let_on_iterator
+)]
 mod test;
+/// Native passphrase verification against a `PgpHash`.
+mod verify;
 
-use clap::{Parser, ValueEnum};
-use convert::{secretkey_to_pgphash, secretsubkey_to_pgphash};
+#[cfg(feature = "audit")]
+pub use audit::{Policy, Report};
+#[cfg(feature = "cost")]
+pub use cost::{Calibration, Cost};
+#[cfg(feature = "decrypt")]
+pub use decrypt::{decrypt_message, Message};
+#[cfg(feature = "encoding")]
+pub use encoding::Encoding;
+pub use error::Error;
+pub use extract::{handle_packet, Artefact, DearmorMode, Extracted, Extractor, Record};
 use hash::PgpHash;
-pub use parse::parse_hash;
-use pgp::{
-    armor::Dearmor,
-    packet::{Packet, PacketParser},
-};
-use std::{error::Error, io::Read, path::PathBuf};
+pub use parse::{check_hash, parse_hash, FieldError};
+#[cfg(feature = "personal")]
+pub use personal::Hints;
+pub use potfile::Potfile;
+#[cfg(feature = "protect")]
+pub use protect::{reprotect, Protection};
+use std::{collections::HashMap, path::PathBuf};
 
 /// A UserID of the form "John Smith (A friend) <john@smith.com>"
 #[derive(Debug, Clone)]
@@ -25,12 +81,43 @@ pub struct UserInfo {
     pub email: String,
 }
 
-pub enum Artefact {
-    Hash(PgpHash),
-    User(UserInfo),
+impl From<&str> for UserInfo {
+    fn from(id: &str) -> Self {
+        let mut name = id.trim().to_string();
+        // get email in <...>, if exists
+        let email = match name.find('<') {
+            None => String::new(),
+            Some(i) => {
+                let email = name.split_off(i);
+                name = name.trim().to_owned();
+                email
+            }
+        };
+        // get comment in (...), if exists
+        let comment = match name.find('(') {
+            None => String::new(),
+            Some(i) => {
+                let comment = name.split_off(i);
+                name = name.trim().to_owned();
+                comment
+            }
+        };
+        UserInfo {
+            name,
+            comment,
+            email,
+        }
+    }
 }
 
-#[derive(Clone, Debug, ValueEnum)]
+/// A record, and all files in which it was found
+pub struct HashEntry {
+    pub record: Record,
+    pub sources: Vec<PathBuf>,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
 pub enum HashFormat {
     /// Format used by John the Ripper
     John,
@@ -38,171 +125,68 @@ pub enum HashFormat {
     Hashcat,
 }
 
-/// Extract hashcat/john hashes from encrypted secret keys in the OpenPGP format
-#[derive(Parser)]
-#[command(version)]
-pub struct Cli {
-    /// The file containing the encrypted secret key
-    pub path: PathBuf,
-    /// The format in which to output the hash
-    #[clap(short, long, value_enum, default_value_t=HashFormat::Hashcat)]
-    pub format: HashFormat,
-    /// don't dearmor the given file
-    #[clap(long)]
-    pub _no_dearmor: bool,
-    /// don't extract hashes from subkeys
-    #[clap(long)]
-    pub _no_subkeys: bool,
-}
-
-pub fn extract_hash(args: &Cli) -> Result<String, Box<dyn Error>> {
-    let mut f = std::fs::File::open(&args.path).expect("could not open the specified file");
-    let mut buf = Vec::new();
-    let read_bytes = match args._no_dearmor {
-        true => f.read_to_end(&mut buf),
-        false => Dearmor::new(f).read_to_end(&mut buf),
-    }
-    .unwrap();
-    log::info!("read {read_bytes} bytes from file {:?}", args.path);
-
-    Ok(handle_file(&buf[..], &args))
-}
-
-fn handle_file(data: &[u8], args: &Cli) -> String {
-    let mut ret = String::new();
-    let parser = PacketParser::new(data);
-
-    let mut hashes = Vec::new();
-
-    let mut user = None;
-    let mut hash = None;
-
-    for item in parser {
-        match item {
-            Ok(packet) => {
-                if let Some(art) = handle_packet(packet, args._no_subkeys).unwrap() {
-                    match art {
-                        Artefact::Hash(h) => {
-                            if user.is_some() {
-                                hashes.push((h, user.unwrap()));
-                                user = None;
-                            } else {
-                                hash = Some(h);
-                            }
-                        }
-                        Artefact::User(u) => {
-                            if hash.is_some() {
-                                hashes.push((hash.unwrap(), u));
-                                hash = None;
-                            } else {
-                                user = Some(u);
-                            }
-                        }
+/// Merge entries of the same key (same fingerprint and protection parameters) into one,
+/// keeping the first entry and adding the sources of the others.
+pub fn merge_duplicates(entries: Vec<HashEntry>) -> Vec<HashEntry> {
+    let mut merged: Vec<HashEntry> = Vec::new();
+    let mut seen: HashMap<(Option<Vec<u8>>, PgpHash), usize> = HashMap::new();
+    for entry in entries {
+        let key = (entry.record.fingerprint.clone(), entry.record.hash.clone());
+        match seen.get(&key) {
+            Some(&i) => {
+                log::info!("skipping duplicate key in {:?}", entry.sources);
+                for path in entry.sources {
+                    if !merged[i].sources.contains(&path) {
+                        merged[i].sources.push(path);
                     }
                 }
             }
-            Err(e) => eprintln!("Error getting a packet: {e}"),
-        }
-    }
-
-    for (h, u) in hashes {
-        match args.format {
-            HashFormat::John => {
-                //<username>:<hash>:::<name_with_email>::<filename>
-                let comment_str = if u.comment.len() > 0 {
-                    String::from(" ") + &u.comment
-                } else {
-                    String::from("")
-                };
-                let email_str = if u.email.len() > 0 {
-                    String::from(" ") + &u.email
-                } else {
-                    String::from("")
-                };
-                if ret.len() > 0 {
-                    ret += "\n";
-                }
-                ret += &format!(
-                    "{}:{h}:::{}{}{}::{}",
-                    u.name,
-                    u.name,
-                    comment_str,
-                    email_str,
-                    args.path.to_str().unwrap()
-                );
+            None => {
+                seen.insert(key, merged.len());
+                merged.push(entry);
             }
-            HashFormat::Hashcat => ret += &format!("{h}"),
         }
     }
-
-    ret
+    merged
 }
 
-pub fn handle_packet(packet: Packet, no_subkeys: bool) -> Result<Option<Artefact>, Box<dyn Error>> {
-    Ok(match packet {
-        Packet::SecretKey(x) => {
-            log::info!("got a SecretKey packet");
-            Some(Artefact::Hash(secretkey_to_pgphash(x)?))
-        }
-        Packet::SecretSubkey(x) => match no_subkeys {
-            true => {
-                log::info!("ignoring SecretSubkey packet because --no-subkeys was specified");
-                None
-            }
-            false => Some(Artefact::Hash(secretsubkey_to_pgphash(x)?)),
-        },
-        Packet::Signature(_) => {
-            log::info!("ignoring Signature packet");
-            None
-        }
-        Packet::Trust(_) => {
-            log::info!("ignoring Trust packet");
-            None
-        }
-        Packet::UserId(x) => {
-            let mut name = x.id().to_string().trim().to_string();
-            // get email in <...>, if exists
-            let email = match name.find('<') {
-                None => String::new(),
-                Some(i) => {
-                    let email = name.split_off(i);
-                    name = name.trim().to_owned();
-                    email
-                }
+/// Format a single entry in the given output format.
+/// For john, all source files are listed in the last field, separated by commas.
+pub fn format_entry(entry: &HashEntry, format: &HashFormat) -> String {
+    let hash = &entry.record.hash;
+    match format {
+        HashFormat::John => {
+            // without a user ID (e.g. symmetrically encrypted messages), use the file name
+            let file_name = entry
+                .sources
+                .first()
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let fallback = UserInfo::from(file_name.as_str());
+            let user = entry.record.user.as_ref().unwrap_or(&fallback);
+            //<username>:<hash>:::<name_with_email>::<filename>
+            let comment_str = if !user.comment.is_empty() {
+                String::from(" ") + &user.comment
+            } else {
+                String::from("")
             };
-            // get comment in (...), if exists
-            let comment = match name.find('(') {
-                None => String::new(),
-                Some(i) => {
-                    let comment = name.split_off(i);
-                    name = name.trim().to_owned();
-                    comment
-                }
+            let email_str = if !user.email.is_empty() {
+                String::from(" ") + &user.email
+            } else {
+                String::from("")
             };
-            Some(Artefact::User(UserInfo {
-                name,
-                comment,
-                email,
-            }))
+            let sources = entry
+                .sources
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            format!(
+                "{}:{hash}:::{}{}{}::{}",
+                user.name, user.name, comment_str, email_str, sources
+            )
         }
-        Packet::UserAttribute(_) => {
-            log::info!("ignoring UserAttribute packet");
-            None
-        }
-        // Packet::SymEncryptedData(_) => todo!(),
-        // Packet::SymEncryptedProtectedData(_) => todo!(),
-        // Packet::SymKeyEncryptedSessionKey(_) => todo!(),
-        // Packet::LiteralData(_) => todo!(),
-        // Packet::Marker(_) => todo!(),
-        // Packet::ModDetectionCode(_) => todo!(),
-        // Packet::OnePassSignature(_) => todo!(),
-        // Packet::PublicKeyEncryptedSessionKey(_) => todo!(),
-        // Packet::CompressedData(_) => todo!(),
-        // Packet::PublicKey(_) => todo!(),
-        // Packet::PublicSubkey(_) => todo!(),
-        _ => {
-            log::info!("ignoring unhandled packet");
-            None
-        }
-    })
+        HashFormat::Hashcat => format!("{hash}"),
+    }
 }
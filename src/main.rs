@@ -1,13 +1,84 @@
-use clap::Parser;
-use pgp2hc::{extract_hash, Cli, HashFormat};
+use clap::{error::ErrorKind, CommandFactory, FromArgMatches};
+use pgp2hc::{
+    cli::{
+        audit, benchmark, check, crack, decrypt, export, extract_hashes, info, reprotect, show,
+        wordlist, Cli, Command,
+    },
+    format_entry, Error, HashFormat, Report,
+};
 
 fn main() {
     env_logger::init();
-    let args = Cli::parse();
+    let mut cli = Cli::command();
+    let matches = cli.get_matches_mut();
+    let args = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    if let Some(command) = &args.command {
+        let result = match command {
+            Command::Crack(crack_args) => crack(crack_args).map(|_| ()),
+            Command::Wordlist(wordlist_args) => wordlist(wordlist_args).map(|_| ()),
+            Command::Show(show_args) => show(show_args).map(|_| ()),
+            Command::Export(export_args) => export(export_args).map(|_| ()),
+            Command::Decrypt(decrypt_args) => decrypt(decrypt_args).map(|_| ()),
+            Command::Reprotect(reprotect_args) => reprotect(reprotect_args).map(|_| ()),
+            Command::Info(info_args) => info(info_args).map(|_| ()),
+            Command::Benchmark(benchmark_args) => benchmark(benchmark_args).map(|_| ()),
+            Command::Check(check_args) => check(check_args).map(|invalid| {
+                if invalid > 0 {
+                    std::process::exit(2);
+                }
+            }),
+            Command::Audit(audit_args) => audit(audit_args).map(|reports| {
+                if reports.iter().any(|r| r.violations() > 0) {
+                    std::process::exit(2);
+                }
+                // keys that could not be audited fail the audit as well
+                if !reports.iter().all(Report::is_complete) {
+                    std::process::exit(3);
+                }
+            }),
+        };
+        if let Err(Error::InvalidArguments(msg)) = &result {
+            // report conflicts found after parsing like the ones clap finds itself
+            let name = matches.subcommand_name().expect("a subcommand");
+            let subcommand = cli.find_subcommand_mut(name).expect("a known subcommand");
+            subcommand.error(ErrorKind::ArgumentConflict, msg).exit();
+        }
+        if let Err(e) = result {
+            eprintln!("error: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
 
     if let HashFormat::John = args.format {
-        eprintln!("\nFile {}", args.path.to_str().unwrap());
+        for path in &args.paths {
+            eprintln!("\nFile {}", path.display());
+        }
+    }
+    let entries = match extract_hashes(&args) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("error: {e}");
+            std::process::exit(1);
+        }
+    };
+    for entry in entries {
+        // the john format lists the source files itself, hashcat lines must only contain the hash
+        if let (true, HashFormat::Hashcat) = (args.dedup, &args.format) {
+            let sources: Vec<_> = entry
+                .sources
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect();
+            eprintln!("found in: {}", sources.join(", "));
+        }
+        if entry.record.password.is_some() {
+            eprintln!(
+                "note: {}: already cracked, see `pgp2hc show`",
+                entry.record.source
+            );
+        }
+        println!("{}", format_entry(&entry, &args.format));
     }
-    let output = extract_hash(&args).unwrap();
-    println!("{}", output);
 }
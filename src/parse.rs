@@ -1,171 +1,206 @@
+use crate::error::Error;
 use crate::hash::*;
+use std::fmt::{self, Display};
 
-fn parse_extra_fields<'a>(
-    mut iter: impl Iterator<Item = &'a str>,
-    n_expected: usize,
-) -> Result<Vec<Vec<u8>>, Box<dyn std::error::Error>> {
-    let mut ret = Vec::new();
-    for i in 0..n_expected {
-        let num_bytes = str::parse::<usize>(&iter.next().ok_or("not enough tokens in hash")?)?;
-        let data = hex::decode(iter.next().ok_or("not enough tokens in hash")?)?;
-        if data.len() != num_bytes {
-            return Err(format!("invalid byte length in extra data field {i}").into());
+/// A field of a hash that is missing or invalid, see [`check_hash`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldError {
+    /// The position of the field among the `*`-separated fields after `$gpg$`, starting at 1.
+    /// 0 for the `$gpg$` prefix.
+    pub index: usize,
+    /// The name of the field, as in [`PgpHash`] and [`ExtraData`]. The lengths of the extra
+    /// data fields are named e.g. `p_len`.
+    pub name: &'static str,
+    pub reason: String,
+}
+
+impl Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "field {} ({}): {}", self.index, self.name, self.reason)
+    }
+}
+
+impl From<FieldError> for Error {
+    fn from(e: FieldError) -> Self {
+        Error::HashParse(e.to_string())
+    }
+}
+
+/// The `*`-separated fields of a hash, numbered from 1
+struct Fields<'a> {
+    tokens: std::str::Split<'a, char>,
+    index: usize,
+}
+
+impl<'a> Fields<'a> {
+    /// The next field, named `name`
+    fn next(&mut self, name: &'static str) -> Result<&'a str, FieldError> {
+        self.index += 1;
+        self.tokens
+            .next()
+            .ok_or_else(|| self.error(name, "missing, the hash ends too early"))
+    }
+
+    /// An error in the last field
+    fn error(&self, name: &'static str, reason: impl Into<String>) -> FieldError {
+        FieldError {
+            index: self.index,
+            name,
+            reason: reason.into(),
+        }
+    }
+
+    fn number(&mut self, name: &'static str) -> Result<usize, FieldError> {
+        let token = self.next(name)?;
+        token
+            .parse()
+            .map_err(|e| self.error(name, format!("invalid number '{token}': {e}")))
+    }
+
+    fn hex(&mut self, name: &'static str) -> Result<Vec<u8>, FieldError> {
+        let token = self.next(name)?;
+        hex::decode(token).map_err(|e| self.error(name, format!("invalid hex string: {e}")))
+    }
+
+    /// An ID of one of the enums of the hash format
+    fn id<T>(&mut self, name: &'static str, from: fn(i32) -> Option<T>) -> Result<T, FieldError> {
+        let token = self.next(name)?;
+        let id = token
+            .parse()
+            .map_err(|e| self.error(name, format!("invalid number '{token}': {e}")))?;
+        from(id).ok_or_else(|| self.error(name, format!("unknown value {id}")))
+    }
+
+    /// A length field named `len`, followed by the hex string `name` of that length
+    fn sized_hex(&mut self, len: &'static str, name: &'static str) -> Result<Vec<u8>, FieldError> {
+        let expected = self.number(len)?;
+        let data = self.hex(name)?;
+        if data.len() != expected {
+            return Err(self.error(
+                name,
+                format!("{} bytes long, but {len} is {expected}", data.len()),
+            ));
         }
-        ret.push(data);
+        Ok(data)
     }
-    Ok(ret)
 }
 
-fn parse_extra_data<'a>(
-    iter: impl Iterator<Item = &'a str>,
+fn parse_extra_data(
+    fields: &mut Fields,
     usage: Usage,
     s2k: StringToKey,
     alg: Algorithm,
-) -> Result<Option<ExtraData>, Box<dyn std::error::Error>> {
+) -> Result<Option<ExtraData>, FieldError> {
     if usage != Usage::TwoFiveFive {
         return Ok(None);
     }
     Ok(match (s2k, alg) {
-        (StringToKey::Salted, Algorithm::DSA) => {
-            let mut x = parse_extra_fields(iter, 4)?.into_iter();
-            // unwrap OK because function above verifies that we have 4
+        (StringToKey::Salted | StringToKey::IteratedSalted, Algorithm::DSA) => {
             Some(ExtraData::Dsa {
-                p: x.next().unwrap(),
-                q: x.next().unwrap(),
-                g: x.next().unwrap(),
-                y: x.next().unwrap(),
+                p: fields.sized_hex("p_len", "p")?,
+                q: fields.sized_hex("q_len", "q")?,
+                g: fields.sized_hex("g_len", "g")?,
+                y: fields.sized_hex("y_len", "y")?,
             })
         }
-        (StringToKey::Salted, Algorithm::ElGamal) => {
-            let mut x = parse_extra_fields(iter, 3)?.into_iter();
+        (StringToKey::Salted | StringToKey::IteratedSalted, Algorithm::ElGamal) => {
             Some(ExtraData::ElGamal {
-                p: x.next().unwrap(),
-                g: x.next().unwrap(),
-                y: x.next().unwrap(),
+                p: fields.sized_hex("p_len", "p")?,
+                g: fields.sized_hex("g_len", "g")?,
+                y: fields.sized_hex("y_len", "y")?,
             })
         }
-        (StringToKey::Salted, _) => Some(ExtraData::Rsa {
-            p: parse_extra_fields(iter, 1)?.into_iter().next().unwrap(),
-        }),
-        (StringToKey::IteratedSalted, Algorithm::DSA) => {
-            let mut x = parse_extra_fields(iter, 4)?.into_iter();
-            Some(ExtraData::Dsa {
-                p: x.next().unwrap(),
-                q: x.next().unwrap(),
-                g: x.next().unwrap(),
-                y: x.next().unwrap(),
+        (StringToKey::Salted, _) | (StringToKey::IteratedSalted, Algorithm::RSAEncSign) => {
+            Some(ExtraData::Rsa {
+                p: fields.sized_hex("p_len", "p")?,
             })
         }
-        (StringToKey::IteratedSalted, Algorithm::ElGamal) => {
-            let mut x = parse_extra_fields(iter, 3)?.into_iter();
-            Some(ExtraData::ElGamal {
-                p: x.next().unwrap(),
-                g: x.next().unwrap(),
-                y: x.next().unwrap(),
-            })
-        }
-        (StringToKey::IteratedSalted, Algorithm::RSAEncSign) => Some(ExtraData::Rsa {
-            p: parse_extra_fields(iter, 1)?.into_iter().next().unwrap(),
-        }),
         _ => None,
     })
 }
 
-pub fn parse_hash(input: &str) -> Result<PgpHash, Box<dyn std::error::Error>> {
+pub fn parse_hash(input: &str) -> Result<PgpHash, Error> {
+    Ok(check_hash(input)?)
+}
+
+/// Parse a hash like [`parse_hash`], returning the first field that is missing or invalid
+pub fn check_hash(input: &str) -> Result<PgpHash, FieldError> {
     if !input.starts_with("$gpg$*") {
-        return Err("invalid prefix, must be '$pgp$'".into());
+        return Err(FieldError {
+            index: 0,
+            name: "prefix",
+            reason: "invalid prefix, must be '$gpg$'".into(),
+        });
     }
-    let mut iter = input[6..].split("*");
-    let algorithm = Algorithm::from_repr(str::parse::<i32>(
-        iter.next().ok_or("not enough tokens in hash")?,
-    )?)
-    .ok_or("invalid value for 'algorithm'")?;
-    let data_len = str::parse::<usize>(iter.next().ok_or("not enough tokens in hash")?)?;
+    let mut fields = Fields {
+        tokens: input[6..].split('*'),
+        index: 0,
+    };
+    let algorithm = fields.id("algorithm", Algorithm::from_repr)?;
+    let data_len = fields.number("data_len")?;
     let bits = match algorithm {
         Algorithm::Symmetric => None,
-        _ => Some(str::parse::<usize>(
-            iter.next().ok_or("not enough tokens in hash")?,
-        )?),
+        _ => Some(fields.number("bits")?),
     };
-    let data = hex::decode(iter.next().ok_or("not enough tokens in hash")?)?;
-    let s2k = StringToKey::from_repr(str::parse::<i32>(
-        iter.next().ok_or("not enough tokens in hash")?,
-    )?)
-    .ok_or("invalid value for 's2k'")?;
-    let usage = Usage::from_repr(str::parse::<i32>(
-        iter.next().ok_or("not enough tokens in hash")?,
-    )?)
-    .ok_or("invalid value for 'usage'")?;
-    let hash_algorithm = HashAlgorithm::from_repr(str::parse::<i32>(
-        iter.next().ok_or("not enough tokens in hash")?,
-    )?)
-    .ok_or("invalid value for 'hash_algorithm'")?;
-    let cipher_algorithm = CipherAlgorithm::from_repr(str::parse::<i32>(
-        iter.next().ok_or("not enough tokens in hash")?,
-    )?)
-    .ok_or("invalid value for 'cipher_algorithm'")?;
+    let data = fields.hex("data")?;
+    if data.len() != data_len {
+        return Err(fields.error(
+            "data",
+            format!("{} bytes long, but data_len is {data_len}", data.len()),
+        ));
+    }
+    let s2k = fields.id("s2k", StringToKey::from_repr)?;
+    let usage = fields.id("usage", Usage::from_repr)?;
+    match algorithm {
+        Algorithm::Symmetric => {
+            if ![Usage::Nine, Usage::Eighteen].contains(&usage) {
+                return Err(fields.error(
+                    "usage",
+                    "for algorithm=Unknown (=0) (symmetric mode), usage must be either 9 or 18",
+                ));
+            }
+        }
+        _ => {
+            if ![Usage::Zero, Usage::TwoFiveFour, Usage::TwoFiveFive].contains(&usage) {
+                return Err(fields.error(
+                    "usage",
+                    "for algorithm != Unknown (not 0), usage must be either 0, 254 or 255",
+                ));
+            }
+        }
+    }
+    let hash_algorithm = fields.id("hash_algorithm", HashAlgorithm::from_repr)?;
+    let cipher_algorithm = fields.id("cipher_algorithm", CipherAlgorithm::from_repr)?;
     // IV only if not in symmetric mode
     let (iv_len, iv) = match algorithm {
         Algorithm::Symmetric => (None, None),
         _ => {
-            let iv_len = str::parse::<usize>(iter.next().ok_or("not enough tokens in hash")?)?;
-            let iv = hex::decode(iter.next().ok_or("not enough tokens in hash")?)?;
+            let iv_len = fields.number("iv_len")?;
+            let iv = fields.hex("iv")?;
+            if iv.len() != iv_len {
+                return Err(fields.error(
+                    "iv",
+                    format!("{} bytes long, but iv_len is {iv_len}", iv.len()),
+                ));
+            }
             (Some(iv_len), Some(iv))
         }
     };
     // count/salt only if we have a salted hash
-    let (count, salt_vec) = match s2k {
+    let (count, salt) = match s2k {
         StringToKey::Simple => (None, None),
         _ => {
-            let count = str::parse::<usize>(iter.next().ok_or("not enough tokens in hash")?)?;
-            let salt_vec = hex::decode(iter.next().ok_or("not enough tokens in hash")?)?;
-            (Some(count), Some(salt_vec))
+            let count = fields.number("count")?;
+            let salt: [u8; 8] = fields.hex("salt")?.try_into().map_err(|v: Vec<u8>| {
+                fields.error("salt", format!("{} bytes long, but must be 8", v.len()))
+            })?;
+            (Some(count), Some(salt))
         }
     };
     // handle extra data
-    let extra_data = parse_extra_data(iter, usage, s2k, algorithm)?;
-
-    // checks
-    if data.len() != data_len {
-        return Err("data length does not match specified value".into());
-    }
-    if let Some(x) = &iv {
-        if let Some(y) = iv_len {
-            if x.len() != y {
-                return Err("IV length does not match specified value".into());
-            }
-        }
-    }
-    let salt = match salt_vec {
-        Some(v) => {
-            if v.len() != 8 {
-                return Err(format!(
-                    "salt length does not match required value (is: {}, should be: 8)",
-                    v.len()
-                )
-                .into());
-            }
-            let salt: [u8; 8] = v[..8].try_into()?;
-            Some(salt)
-        }
-        None => None,
-    };
-    match algorithm {
-        Algorithm::Symmetric => {
-            if ![Usage::Nine, Usage::Eighteen].contains(&usage) {
-                return Err(
-                    "for algorithm=Unknown (=0) (symmetric mode), usage must be either 9 or 18"
-                        .into(),
-                );
-            }
-        }
-        _ => {
-            if ![Usage::Zero, Usage::TwoFiveFour, Usage::TwoFiveFive].contains(&usage) {
-                return Err(
-                    "for algorithm != Unknown (not 0), usage must be either 0, 254 or 255".into(),
-                );
-            }
-        }
+    let extra_data = parse_extra_data(&mut fields, usage, s2k, algorithm)?;
+    if fields.next("end").is_ok() {
+        return Err(fields.error("end", "unexpected field after the hash"));
     }
 
     // if we reach this point, everything is OK!
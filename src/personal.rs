@@ -0,0 +1,257 @@
+use crate::{extract::Record, UserInfo};
+use chrono::{DateTime, Datelike, Utc};
+use std::collections::HashSet;
+
+/// Years before and after the creation of a key that are used as suffixes
+const YEARS_BEFORE: i32 = 3;
+const YEARS_AFTER: i32 = 1;
+
+/// Suffixes that are commonly appended to a password, most common first
+const SUFFIXES: [&str; 22] = [
+    "1", "12", "123", "1234", "12345", "!", "1!", "123!", "!!", "?", "@", "#", "$", "*", "0", "2",
+    "01", "11", "00", "99", "69", "007",
+];
+
+/// Separators between the parts of a name
+const SEPARATORS: [&str; 5] = ["", ".", "_", "-", " "];
+
+/// What is known about the owner of a key, to build a personalised wordlist.
+///
+/// Hints are collected from user IDs (name, comment and email address), the creation time of the
+/// keys, and words and years given by the user, e.g. names of pets, places or a year of birth.
+#[derive(Clone, Debug, Default)]
+pub struct Hints {
+    /// Names, split into their parts (e.g. first and last name)
+    names: Vec<Vec<String>>,
+    /// Single words, e.g. from comments and email addresses
+    words: Vec<String>,
+    /// Numbers found in the hints, e.g. in `alice84@example.com`
+    numbers: Vec<String>,
+    years: Vec<i32>,
+}
+
+impl Hints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the name, comment and email address of a user ID
+    pub fn add_user(&mut self, user: &UserInfo) {
+        self.add_name(&user.name);
+        for word in parts(&user.comment) {
+            // skip short filler words of comments like "(a friend)"
+            if word.chars().count() >= 3 {
+                self.add_word(&word);
+            }
+        }
+        let email = user.email.trim_matches(|c| c == '<' || c == '>');
+        if let Some((local, domain)) = email.split_once('@') {
+            self.add_word(local);
+            self.add_name(local);
+            if let Some(label) = domain.split('.').next() {
+                self.add_word(label);
+            }
+        }
+    }
+
+    /// Add the user ID of the key and the years around its creation
+    pub fn add_record(&mut self, record: &Record) {
+        if let Some(user) = &record.user {
+            self.add_user(user);
+        }
+        if let Some(created) = record.created {
+            self.add_created(created);
+        }
+    }
+
+    /// Add the years around a key creation time
+    pub fn add_created(&mut self, created: DateTime<Utc>) {
+        let year = created.year();
+        for y in year - YEARS_BEFORE..=year + YEARS_AFTER {
+            self.add_year(y);
+        }
+    }
+
+    /// Add a word, e.g. the name of a pet. Digits in the word are also used as suffixes, and a
+    /// hint of several words is also combined like a name.
+    pub fn add_word(&mut self, word: &str) {
+        let word = word.trim();
+        if word.is_empty() {
+            return;
+        }
+        if word.contains(char::is_whitespace) {
+            self.add_name(word);
+        }
+        push_unique(&mut self.words, word.to_string());
+        let letters: String = word.chars().filter(|c| !c.is_ascii_digit()).collect();
+        if !letters.is_empty() && letters != word {
+            push_unique(&mut self.words, letters);
+        }
+        for number in word.split(|c: char| !c.is_ascii_digit()) {
+            if number.len() >= 2 {
+                push_unique(&mut self.numbers, number.to_string());
+            }
+        }
+    }
+
+    /// Add a year, e.g. a year of birth
+    pub fn add_year(&mut self, year: i32) {
+        if !self.years.contains(&year) {
+            self.years.push(year);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.words.is_empty() && self.years.is_empty()
+    }
+
+    /// Generate the candidates, most likely first and without duplicates.
+    ///
+    /// The base words are the names with their parts in every order and with common separators,
+    /// initials, and the single words, each in lower case, capitalised and in upper case.
+    /// They are followed by the base words with years and numbers appended, then with common
+    /// suffixes, and finally by the years and numbers alone.
+    pub fn candidates(&self) -> Vec<String> {
+        let mut seen = HashSet::new();
+        let mut out = Vec::new();
+        let mut push = |candidate: String| {
+            if !candidate.is_empty() && seen.insert(candidate.clone()) {
+                out.push(candidate);
+            }
+        };
+
+        let bases = self.bases();
+        let mut numbers = Vec::new();
+        for year in &self.years {
+            numbers.push(year.to_string());
+            numbers.push(format!("{:02}", year.rem_euclid(100)));
+        }
+        numbers.extend(self.numbers.iter().cloned());
+
+        for base in &bases {
+            push(base.clone());
+        }
+        for number in &numbers {
+            for base in &bases {
+                push(format!("{base}{number}"));
+            }
+        }
+        for suffix in SUFFIXES {
+            for base in &bases {
+                push(format!("{base}{suffix}"));
+            }
+        }
+        for year in &self.years {
+            for base in &bases {
+                push(format!("{base}{year}!"));
+            }
+        }
+        for number in numbers {
+            push(number);
+        }
+        out
+    }
+
+    fn add_name(&mut self, name: &str) {
+        let parts = parts(name);
+        if parts.is_empty() {
+            return;
+        }
+        for part in &parts {
+            push_unique(&mut self.words, part.clone());
+        }
+        if parts.len() > 1 && !self.names.contains(&parts) {
+            self.names.push(parts);
+        }
+    }
+
+    /// The base words, in all case styles
+    fn bases(&self) -> Vec<String> {
+        // parts of names and the separator to join them with
+        let mut combined: Vec<(Vec<String>, &str)> = Vec::new();
+        for parts in &self.names {
+            // more than three parts would give too many permutations
+            let parts = &parts[..parts.len().min(3)];
+            for order in permutations(parts.len()) {
+                let ordered: Vec<String> = order.iter().map(|&i| parts[i].clone()).collect();
+                for sep in SEPARATORS {
+                    combined.push((ordered.clone(), sep));
+                }
+            }
+            // initials, e.g. "js", "jsmith" and "johns"
+            let initial = |p: &String| p.chars().take(1).collect::<String>();
+            let first = &parts[0];
+            let last = &parts[parts.len() - 1];
+            combined.push((parts.iter().map(initial).collect(), ""));
+            combined.push((vec![initial(first), last.clone()], ""));
+            combined.push((vec![first.clone(), initial(last)], ""));
+        }
+
+        let mut bases = Vec::new();
+        for word in &self.words {
+            push_unique(&mut bases, word.clone());
+        }
+        for style in [
+            str::to_lowercase as fn(&str) -> String,
+            capitalize,
+            str::to_uppercase,
+        ] {
+            for word in &self.words {
+                push_unique(&mut bases, style(word));
+            }
+            for (parts, sep) in &combined {
+                let styled: Vec<String> = parts.iter().map(|p| style(p)).collect();
+                push_unique(&mut bases, styled.join(sep));
+            }
+        }
+        bases
+    }
+}
+
+/// Words of `text`, split at everything but letters and digits
+fn parts(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|p| !p.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Lower case with the first letter in upper case
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+/// All orders of 2 to `n` out of `n` items, as indices
+fn permutations(n: usize) -> Vec<Vec<usize>> {
+    let mut out = Vec::new();
+    let mut current = Vec::new();
+    fn extend(n: usize, current: &mut Vec<usize>, out: &mut Vec<Vec<usize>>) {
+        if current.len() >= 2 {
+            out.push(current.clone());
+        }
+        for i in 0..n {
+            if !current.contains(&i) {
+                current.push(i);
+                extend(n, current, out);
+                current.pop();
+            }
+        }
+    }
+    extend(n, &mut current, &mut out);
+    // the natural order first
+    out.sort_by_key(|order| (std::cmp::Reverse(order.len()), order.clone()));
+    out
+}
+
+fn push_unique(list: &mut Vec<String>, item: String) {
+    if !list.contains(&item) {
+        list.push(item);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{error::Error, hash::PgpHash};
+use sha1::{Digest, Sha1};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+/// Marks a hash that john shortened in its potfile, followed by a digest of the full hash
+const JOHN_SOURCE_HASH: &str = "$SOURCE_HASH$";
+
+/// Cracked passphrases from john and hashcat potfiles.
+///
+/// Both write `hash:password` lines, with passphrases that are not printable UTF-8 in the
+/// `$HEX[...]` notation. John shortens long hashes to a prefix followed by `$SOURCE_HASH$` and
+/// the start of the SHA-1 digest of the full hash in hex, such hashes are matched by both.
+/// Lines of other hash types are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct Potfile {
+    passwords: HashMap<String, Vec<u8>>,
+    /// prefixes and digests of hashes shortened by john
+    prefixes: Vec<(String, String, Vec<u8>)>,
+}
+
+impl Potfile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn read(reader: impl BufRead) -> Result<Self, Error> {
+        let mut potfile = Self::new();
+        for line in reader.split(b'\n') {
+            let mut line = line?;
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            // the hash ends at the first colon, the passphrase may contain any bytes
+            let Some(colon) = line.iter().position(|&b| b == b':') else {
+                continue;
+            };
+            let Ok(hash) = std::str::from_utf8(&line[..colon]) else {
+                continue;
+            };
+            if !hash.starts_with("$gpg$") {
+                continue;
+            }
+            let password = decode_password(&line[colon + 1..]);
+            match hash.split_once(JOHN_SOURCE_HASH) {
+                Some((prefix, digest)) if !digest.is_empty() => potfile.prefixes.push((
+                    prefix.to_string(),
+                    digest.to_ascii_lowercase(),
+                    password,
+                )),
+                Some(_) => continue,
+                None => {
+                    potfile.passwords.insert(hash.to_string(), password);
+                }
+            }
+        }
+        Ok(potfile)
+    }
+
+    /// Add a cracked passphrase
+    pub fn insert(&mut self, hash: &PgpHash, password: &[u8]) {
+        self.passwords.insert(hash.to_string(), password.to_vec());
+    }
+
+    /// The passphrase of `hash`, if it was cracked
+    pub fn get(&self, hash: &PgpHash) -> Option<&[u8]> {
+        let hash = hash.to_string();
+        match self.passwords.get(&hash) {
+            Some(password) => Some(password),
+            None if self.prefixes.is_empty() => None,
+            None => {
+                let digest = hex::encode(Sha1::digest(&hash));
+                self.prefixes
+                    .iter()
+                    .find(|(prefix, shortened, _)| {
+                        hash.starts_with(prefix.as_str()) && digest.starts_with(shortened.as_str())
+                    })
+                    .map(|(_, _, password)| &password[..])
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.passwords.len() + self.prefixes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A line for a john or hashcat potfile. Passphrases that are not printable UTF-8 are written
+/// in the `$HEX[...]` notation.
+pub fn potfile_line(hash: &PgpHash, password: &[u8]) -> String {
+    format!("{hash}:{}", encode_password(password))
+}
+
+/// A passphrase as written in potfiles: as is, or in the `$HEX[...]` notation if it is not
+/// printable UTF-8
+pub fn encode_password(password: &[u8]) -> String {
+    match std::str::from_utf8(password) {
+        Ok(p) if !p.chars().any(char::is_control) && !p.starts_with("$HEX[") => p.to_string(),
+        _ => format!("$HEX[{}]", hex::encode(password)),
+    }
+}
+
+/// The bytes of a passphrase from a potfile, decoding the `$HEX[...]` notation
+pub fn decode_password(password: &[u8]) -> Vec<u8> {
+    password
+        .strip_prefix(b"$HEX[")
+        .and_then(|p| p.strip_suffix(b"]"))
+        .and_then(|p| hex::decode(p).ok())
+        .unwrap_or_else(|| password.to_vec())
+}
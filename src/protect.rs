@@ -0,0 +1,362 @@
+use crate::{
+    error::Error,
+    extract::{dearmor, is_armored},
+    hash::PgpHash,
+};
+use pgp::{
+    crypto::{hash::HashAlgorithm, sym::SymmetricKeyAlgorithm},
+    packet::{PacketParser, PacketTrait, SecretKey, SecretSubkey},
+    ser::Serialize,
+    types::{
+        KeyTrait, KeyVersion, PublicParams, SecretKeyTrait, SecretParams, StringToKey,
+        StringToKeyType, Tag, Version,
+    },
+    Deserializable, SignedSecretKey,
+};
+use std::io::Cursor;
+
+/// The protection of re-encrypted secret keys: an iterated and salted string-to-key specifier
+/// with the SHA-1 hash of the key material (usage 254).
+#[derive(Clone, Debug)]
+pub struct Protection {
+    pub cipher: SymmetricKeyAlgorithm,
+    pub hash: HashAlgorithm,
+    /// The coded iteration count (RFC4880, Section 3.7.1.3)
+    pub count: u8,
+}
+
+impl Default for Protection {
+    /// AES-256 with SHA-256 and the maximal count of 65011712 bytes
+    fn default() -> Self {
+        Protection {
+            cipher: SymmetricKeyAlgorithm::AES256,
+            hash: HashAlgorithm::SHA2_256,
+            count: 0xff,
+        }
+    }
+}
+
+impl Protection {
+    /// The number of bytes that are hashed by the string-to-key function
+    pub fn count_bytes(&self) -> usize {
+        decode_count(self.count)
+    }
+}
+
+/// The smallest coded iteration count that hashes at least `count` bytes, at most 65011712
+pub fn coded_count(count: usize) -> u8 {
+    (0..=0xff)
+        .find(|&c| decode_count(c) >= count)
+        .unwrap_or(0xff)
+}
+
+/// RFC4880, Section 3.7.1.3
+pub(crate) fn decode_count(c: u8) -> usize {
+    (16 + usize::from(c & 15)) << ((c >> 4) + 6)
+}
+
+/// Parse all secret keys of an armored or binary file.
+///
+/// Trust packets, which GnuPG 1 stores in its keyrings after most packets, are skipped: the `pgp`
+/// crate would otherwise end the key at the first one and lose its user IDs and subkeys.
+/// Packets that can't be parsed are an error, so that nothing is lost silently.
+pub fn read_secret_keys(data: &[u8]) -> Result<Vec<SignedSecretKey>, Error> {
+    let data = match is_armored(data) {
+        true => dearmor(data)?,
+        false => data.to_vec(),
+    };
+    let packets = PacketParser::new(Cursor::new(data))
+        .filter(|p| !matches!(p, Ok(p) if p.tag() == Tag::Trust))
+        .collect::<Result<Vec<_>, _>>()?;
+    let keys = SignedSecretKey::from_packets(packets.into_iter().peekable())
+        .collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() {
+        return Err(Error::Packet(pgp::errors::Error::NoMatchingPacket));
+    }
+    Ok(keys)
+}
+
+/// Unlock the primary key and all secret subkeys with `passphrase` and encrypt them again with
+/// `new_passphrase` and `protection`. Keys without a passphrase are protected as well.
+///
+/// The passphrase is checked natively first, as the `pgp` crate doesn't check the hash of the
+/// decrypted key material. Returns [`Error::WrongPassphrase`] if it doesn't unlock a key.
+pub fn reprotect(
+    key: &SignedSecretKey,
+    passphrase: &str,
+    new_passphrase: &str,
+    protection: &Protection,
+) -> Result<SignedSecretKey, Error> {
+    let mut key = key.clone();
+    let passphrases = (passphrase, new_passphrase);
+    key.primary_key = reprotect_packet(&key.primary_key, passphrases, protection)?;
+    for subkey in &mut key.secret_subkeys {
+        subkey.key = reprotect_packet(&subkey.key, passphrases, protection)?;
+    }
+    Ok(key)
+}
+
+/// Like [`reprotect`], for all secret keys and subkeys in binary OpenPGP data, and without
+/// parsing anything but the secret key packets: all other packets (user IDs, signatures, public
+/// keys, trust packets) and the public parts of the secret key packets stay byte-identical.
+///
+/// Stubs without secret key material (e.g. GnuPG's `gnu-dummy` for keys on a smartcard) are
+/// kept as they are. Every re-protected key is checked with the new passphrase before it is
+/// returned. Returns the new data and the number of re-protected keys.
+pub fn reprotect_data(
+    data: &[u8],
+    passphrase: &str,
+    new_passphrase: &str,
+    protection: &Protection,
+) -> Result<(Vec<u8>, usize), Error> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut reprotected = 0;
+    let mut rest = data;
+    while !rest.is_empty() {
+        let packet = RawPacket::split(&mut rest)?;
+        let body = match (packet.tag, packet.body) {
+            (5, Some(body)) => reprotect_body::<SecretKey>(
+                packet.version,
+                body,
+                (passphrase, new_passphrase),
+                protection,
+            )?,
+            (7, Some(body)) => reprotect_body::<SecretSubkey>(
+                packet.version,
+                body,
+                (passphrase, new_passphrase),
+                protection,
+            )?,
+            (5 | 7, None) => {
+                return Err(Error::Packet(pgp::errors::Error::Message(
+                    "secret key packet with a partial length".into(),
+                )))
+            }
+            _ => None,
+        };
+        match body {
+            Some(body) => {
+                packet
+                    .version
+                    .write_header(&mut out, packet.tag, body.len())?;
+                out.extend(body);
+                reprotected += 1;
+            }
+            None => out.extend(packet.raw),
+        }
+    }
+    Ok((out, reprotected))
+}
+
+/// Secret key and subkey packets, which share these methods but not as a trait
+trait SecretPacket: KeyTrait + PacketTrait + Sized {
+    fn from_body(version: Version, body: &[u8]) -> pgp::errors::Result<Self>;
+    /// The serialized public key, with which the packet body starts
+    fn public_body(&self) -> pgp::errors::Result<Vec<u8>>;
+    fn secret(&self) -> &SecretParams;
+    fn public(&self) -> &PublicParams;
+    fn key_version(&self) -> KeyVersion;
+    fn hash(&self) -> Result<PgpHash, Error>;
+}
+
+macro_rules! impl_secret_packet {
+    ($name:ty) => {
+        impl SecretPacket for $name {
+            fn from_body(version: Version, body: &[u8]) -> pgp::errors::Result<Self> {
+                <$name>::from_slice(version, body)
+            }
+
+            fn public_body(&self) -> pgp::errors::Result<Vec<u8>> {
+                self.public_key().to_bytes()
+            }
+
+            fn secret(&self) -> &SecretParams {
+                self.secret_params()
+            }
+
+            fn public(&self) -> &PublicParams {
+                self.public_params()
+            }
+
+            fn key_version(&self) -> KeyVersion {
+                self.version()
+            }
+
+            fn hash(&self) -> Result<PgpHash, Error> {
+                PgpHash::try_from(self)
+            }
+        }
+    };
+}
+
+impl_secret_packet!(SecretKey);
+impl_secret_packet!(SecretSubkey);
+
+/// The secret parameters of `packet`, decrypted with the first and encrypted with the second
+/// passphrase
+fn protect_params(
+    packet: &impl SecretPacket,
+    (passphrase, new_passphrase): (&str, &str),
+    protection: &Protection,
+) -> Result<SecretParams, Error> {
+    let plain = match packet.secret() {
+        SecretParams::Plain(plain) => plain.clone(),
+        SecretParams::Encrypted(encrypted) => {
+            if !packet.hash()?.verify(passphrase)? {
+                return Err(wrong_passphrase(packet));
+            }
+            encrypted.unlock(
+                || passphrase.to_string(),
+                packet.algorithm(),
+                packet.public(),
+            )?
+        }
+    };
+    let mut rng = rand::thread_rng();
+    let s2k = StringToKey::new_iterated(&mut rng, protection.hash, protection.count);
+    Ok(SecretParams::Encrypted(plain.encrypt(
+        &mut rng,
+        new_passphrase,
+        protection.cipher,
+        s2k,
+        packet.key_version(),
+        254,
+    )?))
+}
+
+fn reprotect_packet<K: SecretPacket>(
+    packet: &K,
+    passphrases: (&str, &str),
+    protection: &Protection,
+) -> Result<K, Error> {
+    let mut body = packet.public_body()?;
+    protect_params(packet, passphrases, protection)?.to_writer(&mut body)?;
+    Ok(K::from_body(packet.packet_version(), &body)?)
+}
+
+/// The new body of a secret key packet: the original public part followed by the new secret
+/// parameters. None for stubs without secret key material.
+fn reprotect_body<K: SecretPacket>(
+    version: Version,
+    body: &[u8],
+    passphrases: (&str, &str),
+    protection: &Protection,
+) -> Result<Option<Vec<u8>>, Error> {
+    let packet = K::from_body(version, body)?;
+    if let SecretParams::Encrypted(encrypted) = packet.secret() {
+        if encrypted.string_to_key().typ() as u8 > StringToKeyType::IteratedAndSalted as u8 {
+            log::warn!(
+                "keeping key {} without secret key material ({:?})",
+                hex::encode_upper(packet.key_id()),
+                encrypted.string_to_key().typ()
+            );
+            return Ok(None);
+        }
+    }
+    let public = packet.public_body()?;
+    if !body.starts_with(&public) {
+        return Err(Error::Packet(pgp::errors::Error::Message(format!(
+            "the public part of key {} can't be kept as it is",
+            hex::encode_upper(packet.key_id())
+        ))));
+    }
+    let mut new_body = public;
+    protect_params(&packet, passphrases, protection)?.to_writer(&mut new_body)?;
+
+    let check = K::from_body(version, &new_body)?;
+    if !check.hash()?.verify(passphrases.1)? {
+        return Err(wrong_passphrase(&check));
+    }
+    Ok(Some(new_body))
+}
+
+fn wrong_passphrase(packet: &impl KeyTrait) -> Error {
+    Error::WrongPassphrase(format!("key {}", hex::encode_upper(packet.key_id())))
+}
+
+/// A packet of raw OpenPGP data (RFC4880, Section 4.2)
+pub(crate) struct RawPacket<'a> {
+    pub(crate) tag: u8,
+    pub(crate) version: Version,
+    /// The whole packet, including its header
+    pub(crate) raw: &'a [u8],
+    /// The body, if it doesn't have partial lengths
+    pub(crate) body: Option<&'a [u8]>,
+}
+
+impl<'a> RawPacket<'a> {
+    /// Split the first packet off `data`
+    pub(crate) fn split(data: &mut &'a [u8]) -> Result<Self, Error> {
+        let invalid = || Error::Packet(pgp::errors::Error::Message("invalid packet header".into()));
+        let input = *data;
+        let be = |bytes: Option<&[u8]>| {
+            bytes
+                .map(|b| b.iter().fold(0usize, |n, &b| n << 8 | usize::from(b)))
+                .ok_or_else(invalid)
+        };
+        let header = *input.first().ok_or_else(invalid)?;
+        // the tag, header format, end of the packet and start of the body if it is in one piece
+        let (tag, version, end, body_start) = match header & 0xc0 {
+            0x80 => {
+                let (start, len) = match header & 0x03 {
+                    0 => (2, be(input.get(1..2))?),
+                    1 => (3, be(input.get(1..3))?),
+                    2 => (5, be(input.get(1..5))?),
+                    // indeterminate length, up to the end of the data
+                    _ => (1, input.len() - 1),
+                };
+                ((header >> 2) & 0x0f, Version::Old, start + len, Some(start))
+            }
+            0xc0 => {
+                let mut pos = 1;
+                let mut partial = false;
+                // partial lengths: chunks with a power of two as length, ending with a normal one
+                loop {
+                    let first = *input.get(pos).ok_or_else(invalid)?;
+                    let (header_len, len) = match first {
+                        0..=191 => (1, usize::from(first)),
+                        192..=223 => (2, (be(input.get(pos..pos + 2))? - (192 << 8)) + 192),
+                        255 => (5, be(input.get(pos + 1..pos + 5))?),
+                        _ => (1, 1 << (first & 0x1f)),
+                    };
+                    pos += header_len + len;
+                    if (224..255).contains(&first) {
+                        partial = true;
+                        continue;
+                    }
+                    let start = pos - len;
+                    break (
+                        header & 0x3f,
+                        Version::New,
+                        pos,
+                        (!partial).then_some(start),
+                    );
+                }
+            }
+            _ => return Err(invalid()),
+        };
+        let raw = input.get(..end).ok_or_else(invalid)?;
+        *data = &input[end..];
+        Ok(RawPacket {
+            tag,
+            version,
+            raw,
+            body: body_start.map(|start| &raw[start..]),
+        })
+    }
+
+    /// The public key of a secret key packet without secret key material: GnuPG's `gnu-dummy`
+    /// stub of an offline primary key, or `divert-to-card` for a key on a smartcard. Their
+    /// string-to-key extension 101 can't be parsed by `pgp`.
+    #[cfg(feature = "audit")]
+    pub(crate) fn stub(&self) -> Option<pgp::packet::PublicKey> {
+        let (5 | 7, Some(body)) = (self.tag, self.body) else {
+            return None;
+        };
+        let key = pgp::packet::PublicKey::from_slice(self.version, body).ok()?;
+        match body.strip_prefix(&key.to_bytes().ok()?[..])? {
+            [254 | 255, _, 101, _, b'G', b'N', b'U', 1 | 2, ..] => Some(key),
+            _ => None,
+        }
+    }
+}
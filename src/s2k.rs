@@ -0,0 +1,253 @@
+use crate::{
+    error::Error,
+    hash::{HashAlgorithm, PgpHash, StringToKey},
+};
+use digest::{
+    core_api::{Block, BlockSizeUser, BufferKindUser, CoreProxy, CoreWrapper, UpdateCore},
+    typenum::{IsLess, Le, NonZero, U256},
+    Digest,
+};
+use std::marker::PhantomData;
+
+/// A string-to-key function with the salt, count and key size of one hash, which derives keys
+/// from candidate passphrases
+pub(crate) trait KeyDerivation: Send {
+    fn derive(&mut self, passphrase: &[u8]) -> Vec<u8>;
+}
+
+/// The fastest key derivation for `hash`: [`IteratedS2k`] for iterated and salted
+/// string-to-key functions, the straightforward implementation otherwise. Returns an error if
+/// the digest or cipher is unknown, or the salt or count is missing.
+pub(crate) fn key_derivation(hash: &PgpHash) -> Result<Box<dyn KeyDerivation>, Error> {
+    select(hash, true)
+}
+
+/// The straightforward implementation of the string-to-key function of `hash`, which feeds the
+/// repeated salt and passphrase to the digest one by one. It is the reference for
+/// [`IteratedS2k`].
+#[cfg(any(feature = "cost", test))]
+pub(crate) fn reference_derivation(hash: &PgpHash) -> Result<Box<dyn KeyDerivation>, Error> {
+    select(hash, false)
+}
+
+fn select(hash: &PgpHash, fast: bool) -> Result<Box<dyn KeyDerivation>, Error> {
+    let params = Params::of(hash)?;
+    let fast = fast && hash.s2k == StringToKey::IteratedSalted;
+    macro_rules! derivation {
+        ($digest:ty) => {
+            match fast {
+                true => Box::new(IteratedS2k::<<$digest as CoreProxy>::Core>::new(params)),
+                false => Box::new(Reference::<$digest>(params, PhantomData)),
+            }
+        };
+    }
+    Ok(match hash.hash_algorithm {
+        HashAlgorithm::Unknown => return Err(Error::UnsupportedAlgorithm("unknown digest".into())),
+        HashAlgorithm::MD5 => derivation!(md5::Md5),
+        HashAlgorithm::SHA1 => derivation!(sha1::Sha1),
+        HashAlgorithm::RIPEMD160 => derivation!(ripemd::Ripemd160),
+        HashAlgorithm::SHA256 => derivation!(sha2::Sha256),
+        HashAlgorithm::SHA384 => derivation!(sha2::Sha384),
+        HashAlgorithm::SHA512 => derivation!(sha2::Sha512),
+        HashAlgorithm::SHA224 => derivation!(sha2::Sha224),
+    })
+}
+
+/// The parameters of a string-to-key function
+#[derive(Clone, Copy, Debug)]
+struct Params {
+    /// None for simple string-to-key functions
+    salt: Option<[u8; 8]>,
+    /// 0 unless iterated
+    count: usize,
+    key_size: usize,
+}
+
+impl Params {
+    fn of(hash: &PgpHash) -> Result<Self, Error> {
+        let key_size = hash
+            .cipher_algorithm
+            .key_size()
+            .ok_or_else(|| Error::UnsupportedAlgorithm("unknown cipher".into()))?;
+        let salt = match hash.s2k {
+            StringToKey::Simple => None,
+            StringToKey::Salted | StringToKey::IteratedSalted => Some(
+                hash.salt
+                    .ok_or_else(|| Error::HashParse("missing salt".into()))?,
+            ),
+        };
+        let count = match hash.s2k {
+            StringToKey::IteratedSalted => hash
+                .count
+                .ok_or_else(|| Error::HashParse("missing count".into()))?,
+            _ => 0,
+        };
+        Ok(Params {
+            salt,
+            count,
+            key_size,
+        })
+    }
+}
+
+struct Reference<D>(Params, PhantomData<fn() -> D>);
+
+impl<D: Digest> KeyDerivation for Reference<D> {
+    fn derive(&mut self, passphrase: &[u8]) -> Vec<u8> {
+        s2k::<D>(&self.0, passphrase)
+    }
+}
+
+/// RFC4880, Section 3.7.1: if the digest is shorter than the key, further digests are computed
+/// over the same input preloaded with one, two, ... zero bytes.
+fn s2k<D: Digest>(params: &Params, passphrase: &[u8]) -> Vec<u8> {
+    let salted = params.salt.map(|salt| [&salt[..], passphrase].concat());
+    let count = params.count;
+
+    let mut key = Vec::with_capacity(params.key_size);
+    let mut preload = 0;
+    while key.len() < params.key_size {
+        let mut digest = D::new();
+        digest.update(vec![0; preload]);
+        match &salted {
+            None => digest.update(passphrase),
+            // the whole salt and passphrase are hashed at least once, even for lower counts
+            Some(input) if count <= input.len() => digest.update(input),
+            Some(input) => {
+                let mut remaining = count;
+                while remaining >= input.len() {
+                    digest.update(input);
+                    remaining -= input.len();
+                }
+                digest.update(&input[..remaining]);
+            }
+        }
+        key.extend_from_slice(&digest.finalize());
+        preload += 1;
+    }
+    key.truncate(params.key_size);
+    key
+}
+
+/// The iterated and salted string-to-key function (RFC4880, Section 3.7.1.3) for the
+/// compression function `C` of a digest.
+///
+/// Each digest context hashes its preloaded zero bytes followed by `count` bytes of repeated
+/// salt and passphrase. After the first block, which holds the preload, this data is periodic:
+/// with `n` bytes of salt and passphrase and blocks of `b` bytes, block `k + n / gcd(n, b)`
+/// equals block `k`. The blocks of a few periods are laid out once per candidate and passed
+/// straight to the compression function, as often as they repeat, instead of copying every
+/// repetition of the salt and passphrase through the digest's buffer. Only the last, partial
+/// block goes through the buffer, for the padding.
+///
+/// The blocks are kept between candidates, and only resized if the length of the passphrase
+/// changes. The compression state itself can't be shared between candidates, since the
+/// passphrase is already part of the first block.
+struct IteratedS2k<C: BlockSizeUser> {
+    params: Params,
+    /// length of the salt and passphrase of the last candidate
+    len: usize,
+    /// the salt and passphrase, repeated to fill at least one block at any offset
+    pattern: Vec<u8>,
+    /// the blocks laid out for each context
+    contexts: Vec<Vec<Block<C>>>,
+}
+
+impl<C> IteratedS2k<C>
+where
+    C: UpdateCore + BufferKindUser + Default,
+    C::BlockSize: IsLess<U256>,
+    Le<C::BlockSize, U256>: NonZero,
+    CoreWrapper<C>: Digest,
+{
+    fn new(params: Params) -> Self {
+        let digest_size = <CoreWrapper<C> as Digest>::output_size();
+        IteratedS2k {
+            params,
+            len: 0,
+            pattern: Vec::new(),
+            contexts: vec![Vec::new(); params.key_size.div_ceil(digest_size)],
+        }
+    }
+
+    /// `n` bytes of the repeated salt and passphrase, starting at `offset` of the hashed data
+    fn bytes(&self, offset: usize, n: usize) -> &[u8] {
+        let start = offset % self.len;
+        &self.pattern[start..start + n]
+    }
+
+    /// The digest of context `preload` over the salt and passphrase in `self.pattern`
+    fn hash(&mut self, preload: usize) -> digest::Output<CoreWrapper<C>> {
+        let block = C::block_size();
+        let len = preload + self.params.count;
+        let full_blocks = len / block;
+        // the first block is the only one with the preload, the others repeat every `cycle`
+        // blocks. Whole cycles of at least `RUN` blocks are laid out, so that the compression
+        // function gets long runs of blocks.
+        let cycle = self.len / gcd(self.len, block);
+        let repeated = (full_blocks - 1).min(cycle * RUN.div_ceil(cycle));
+
+        let mut blocks = std::mem::take(&mut self.contexts[preload]);
+        blocks.resize(1 + repeated, Default::default());
+        blocks[0][..preload].fill(0);
+        blocks[0][preload..].copy_from_slice(self.bytes(0, block - preload));
+        for (k, b) in blocks.iter_mut().enumerate().skip(1) {
+            b.copy_from_slice(self.bytes(k * block - preload, block));
+        }
+
+        let mut core = C::default();
+        core.update_blocks(&blocks[..1]);
+        let mut remaining = full_blocks - 1;
+        while remaining > 0 {
+            let n = remaining.min(repeated);
+            core.update_blocks(&blocks[1..1 + n]);
+            remaining -= n;
+        }
+        self.contexts[preload] = blocks;
+        let mut digest = CoreWrapper::from_core(core);
+        Digest::update(
+            &mut digest,
+            self.bytes(full_blocks * block - preload, len % block),
+        );
+        digest.finalize()
+    }
+}
+
+impl<C> KeyDerivation for IteratedS2k<C>
+where
+    C: UpdateCore + BufferKindUser + Default + Send,
+    C::BlockSize: IsLess<U256>,
+    Le<C::BlockSize, U256>: NonZero,
+    CoreWrapper<C>: Digest,
+{
+    fn derive(&mut self, passphrase: &[u8]) -> Vec<u8> {
+        let salt = self.params.salt.expect("iterated and salted");
+        self.len = salt.len() + passphrase.len();
+        // counts below a block or the input are too low to have a period
+        if self.params.count <= self.len || self.params.count < C::block_size() {
+            return s2k::<CoreWrapper<C>>(&self.params, passphrase);
+        }
+        self.pattern.clear();
+        while self.pattern.len() < self.len + C::block_size() {
+            self.pattern.extend_from_slice(&salt);
+            self.pattern.extend_from_slice(passphrase);
+        }
+
+        let mut key = Vec::with_capacity(self.params.key_size);
+        for preload in 0..self.contexts.len() {
+            key.extend_from_slice(&self.hash(preload));
+        }
+        key.truncate(self.params.key_size);
+        key
+    }
+}
+
+/// The minimal number of blocks passed to the compression function at once
+const RUN: usize = 32;
+
+fn gcd(a: usize, b: usize) -> usize {
+    match b {
+        0 => a,
+        b => gcd(b, a % b),
+    }
+}
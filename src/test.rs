@@ -1,10 +1,18 @@
 use super::*;
+#[cfg(feature = "cli")]
+use cli::{extract_hash, extract_hashes, Cli};
+use extract::Warning;
+use hash::{Algorithm, Usage};
 use pgp::{
+    armor::Dearmor,
     crypto::{hash::HashAlgorithm, sym::SymmetricKeyAlgorithm},
+    ser::Serialize,
     types::*,
-    Deserializable, SignedSecretKey,
+    Deserializable, KeyType, SecretKeyParamsBuilder, SignedSecretKey,
 };
 use rsa::traits::{PrivateKeyParts, PublicKeyParts};
+use std::io::Read;
+#[cfg(feature = "cli")]
 use std::{env, ffi::OsString, process::Command};
 
 #[test]
@@ -61,12 +69,17 @@ fn encrypted_private_key() {
 
 /// This test compares to a set of hashes pre-generated with john's gpg2john utility.
 #[test]
+#[cfg(feature = "cli")]
 fn test_john_compatibility() {
     let mut args = Cli {
-        path: "".into(),
+        command: None,
+        paths: vec![],
         format: HashFormat::John,
         _no_dearmor: false,
         _no_subkeys: true,
+        dedup: false,
+        potfile: None,
+        left: false,
     };
     let basedir = PathBuf::from("data");
     let mut index = 1;
@@ -76,7 +89,7 @@ fn test_john_compatibility() {
         if !fname.exists() {
             break;
         }
-        args.path = fname.clone();
+        args.paths = vec![fname.clone()];
 
         // get correct hash
         fname.set_extension("hash");
@@ -87,6 +100,191 @@ fn test_john_compatibility() {
     }
 }
 
+#[test]
+#[cfg(feature = "cli")]
+fn test_dedup() {
+    let key1 = PathBuf::from("data/testkey1.key");
+    let key2 = PathBuf::from("data/testkey2.key");
+    let mut args = Cli {
+        command: None,
+        paths: vec![key1.clone(), key2.clone(), key1.clone()],
+        format: HashFormat::John,
+        _no_dearmor: false,
+        _no_subkeys: true,
+        dedup: false,
+        potfile: None,
+        left: false,
+    };
+    assert_eq!(extract_hashes(&args).unwrap().len(), 3);
+
+    args.dedup = true;
+    let entries = extract_hashes(&args).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].sources, vec![key1.clone()]);
+    assert_eq!(entries[1].sources, vec![key2]);
+
+    // the same key under a different file name is merged into one entry
+    let copy = env::temp_dir().join("pgp2hc_dedup_testkey1.key");
+    std::fs::copy(&key1, &copy).unwrap();
+    args.paths = vec![key1.clone(), copy.clone()];
+    let entries = extract_hashes(&args).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].sources, vec![key1, copy.clone()]);
+    assert!(format_entry(&entries[0], &HashFormat::John)
+        .ends_with(&format!("::data/testkey1.key,{}", copy.to_str().unwrap())));
+    std::fs::remove_file(copy).unwrap();
+}
+
+#[test]
+fn test_extractor() {
+    let data = std::fs::read("data/testkey1.key").unwrap();
+    let records: Vec<_> = Extractor::new()
+        .extract_bytes(&data)
+        .unwrap()
+        .map(|item| match item {
+            Extracted::Record(r) => r,
+            Extracted::Warning(w) => panic!("unexpected warning: {w}"),
+        })
+        .collect();
+    assert_eq!(records.len(), 2);
+    assert!(!records[0].is_subkey);
+    assert!(records[1].is_subkey);
+    assert_ne!(records[0].key_id, records[1].key_id);
+    assert_eq!(records[0].source.packet, Some(0));
+    for r in &records {
+        // the subkey is bound to the user ID of its primary key
+        assert_eq!(r.user.as_ref().unwrap().email, "<fred@bla.com>");
+        assert_eq!(
+            r.key_id.as_ref().unwrap().as_ref(),
+            &r.fingerprint.as_ref().unwrap()[12..]
+        );
+    }
+
+    // builder options
+    let extractor = Extractor::new()
+        .subkeys(false)
+        .dearmor(DearmorMode::Never)
+        .filter(|r| r.hash.bits == Some(2048));
+    assert_eq!(extractor.extract_bytes(&data).unwrap().count(), 1);
+    let extractor = Extractor::new().filter(|r| r.hash.bits == Some(4096));
+    assert_eq!(extractor.extract_bytes(&data).unwrap().count(), 0);
+}
+
+#[test]
+fn test_conversions() {
+    let input = std::fs::read_to_string("data/testkey2.key").unwrap();
+    let (key, _) = SignedSecretKey::from_string(&input).unwrap();
+    let records: Vec<_> = Extractor::new()
+        .extract_bytes(input.as_bytes())
+        .unwrap()
+        .collect();
+
+    let primary = PgpHash::try_from(&key).unwrap();
+    let subkey = PgpHash::try_from(&key.secret_subkeys[0]).unwrap();
+    assert_eq!(PgpHash::try_from(&key.primary_key).unwrap(), primary);
+    assert_eq!(
+        PgpHash::try_from(&key.secret_subkeys[0].key).unwrap(),
+        subkey
+    );
+
+    let from_key = Extractor::new().extract_key(&key);
+    assert_eq!(from_key.len(), 2);
+    for (a, b) in from_key.iter().zip(&records) {
+        match (a, b) {
+            (Extracted::Record(a), Extracted::Record(b)) => {
+                assert_eq!(a.hash, b.hash);
+                assert_eq!(a.key_id, b.key_id);
+                assert_eq!(a.user.as_ref().unwrap().name, "Mr. Test");
+                assert_eq!(a.source.packet, None);
+            }
+            _ => panic!("expected records"),
+        }
+    }
+    assert_eq!(Extractor::new().subkeys(false).extract_key(&key).len(), 1);
+
+    // symmetrically encrypted messages (gpg -c), with and without MDC
+    for (file, usage) in [("symmetric1", Usage::Eighteen), ("symmetric2", Usage::Nine)] {
+        let data = std::fs::read(format!("data/{file}.gpg")).unwrap();
+        let items: Vec<_> = Extractor::new().extract_bytes(&data).unwrap().collect();
+        assert_eq!(items.len(), 1);
+        let Extracted::Record(r) = &items[0] else {
+            panic!("expected a record")
+        };
+        assert_eq!(r.hash.algorithm, Algorithm::Symmetric);
+        assert_eq!(r.hash.usage, usage);
+        assert!(r.key_id.is_none());
+        assert_eq!(parse_hash(&r.hash.to_string()).unwrap(), r.hash);
+    }
+}
+
+/// Generate a signed EdDSA secret key, optionally protected with `passphrase`
+fn generate_key(passphrase: Option<&str>) -> SignedSecretKey {
+    let key = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_create_certificates(true)
+        .can_sign(true)
+        .primary_user_id("Test Key <test@example.com>".into())
+        .passphrase(passphrase.map(String::from))
+        .build()
+        .unwrap()
+        .generate()
+        .unwrap();
+    key.sign(|| passphrase.unwrap_or_default().into()).unwrap()
+}
+
+#[test]
+fn test_malformed_input() {
+    // an unprotected key yields a warning, and the following key is still processed
+    let mut data = generate_key(None).to_bytes().unwrap();
+    let mut keyfile = Vec::new();
+    Dearmor::new(std::fs::File::open("data/testkey1.key").unwrap())
+        .read_to_end(&mut keyfile)
+        .unwrap();
+    data.extend_from_slice(&keyfile);
+    let items: Vec<_> = Extractor::new().extract_bytes(&data).unwrap().collect();
+    assert!(matches!(
+        &items[0],
+        Extracted::Warning(Warning {
+            error: Error::Unprotected,
+            ..
+        })
+    ));
+    assert_eq!(
+        items
+            .iter()
+            .filter(|i| matches!(i, Extracted::Record(_)))
+            .count(),
+        2
+    );
+
+    // garbage after a valid key is reported, but does not panic
+    let mut data = keyfile.clone();
+    data.extend_from_slice(&[0xff; 100]);
+    let items: Vec<_> = Extractor::new().extract_bytes(&data).unwrap().collect();
+    assert!(matches!(items.last(), Some(Extracted::Warning(_))));
+
+    // truncated input
+    for len in [1, 10, 100, 500, keyfile.len() - 1] {
+        for _ in Extractor::new().extract_bytes(&keyfile[..len]).unwrap() {}
+    }
+
+    // broken armor
+    let armored = std::fs::read("data/testkey1.key").unwrap();
+    assert!(matches!(
+        Extractor::new().extract_bytes(&armored[..500]),
+        Err(Error::Armor(_))
+    ));
+
+    assert!(matches!(
+        parse_hash("$gpg$*1*668"),
+        Err(Error::HashParse(_))
+    ));
+    assert!(matches!(
+        parse_hash("$gpg$*1*abc*2048"),
+        Err(Error::HashParse(_))
+    ));
+}
+
 #[test]
 fn test_parse_john_hashes() {
     // test parsing and printing of hashes taken from the john implementation
@@ -96,6 +294,1473 @@ fn test_parse_john_hashes() {
     }
 }
 
+#[test]
+fn test_verify_john_hashes() {
+    for (hashstr, password) in JOHN_HASHES {
+        let hash = parse_hash(hashstr).unwrap();
+        assert!(hash.verify(password).unwrap(), "{hashstr}");
+        assert!(!hash.verify("wrong password").unwrap(), "{hashstr}");
+    }
+}
+
+#[test]
+fn test_verify_fixtures() {
+    // testkey2.pw does not unlock testkey2.key (gpg rejects it, too), so it is left out
+    for name in [
+        "testkey1.key",
+        "ec_testkey1.key",
+        "symmetric1.gpg",
+        "symmetric2.gpg",
+    ] {
+        let path = std::path::Path::new("data").join(name);
+        let password = std::fs::read_to_string(path.with_extension("pw")).unwrap();
+        let records: Vec<_> = Extractor::new()
+            .extract_file(&path)
+            .unwrap()
+            .map(|item| match item {
+                Extracted::Record(r) => r,
+                Extracted::Warning(w) => panic!("{w}"),
+            })
+            .collect();
+        assert!(!records.is_empty(), "{name}");
+        for record in records {
+            assert!(record.hash.verify(password.trim_end()).unwrap(), "{name}");
+            assert!(!record.hash.verify("wrong password").unwrap(), "{name}");
+        }
+    }
+
+    // complete usage 18 data must end with the MDC, only cut data is checked for a plausible packet
+    let data = std::fs::read("data/symmetric1.gpg").unwrap();
+    let Extracted::Record(record) = Extractor::new()
+        .extract_bytes(&data)
+        .unwrap()
+        .next()
+        .unwrap()
+    else {
+        panic!("expected a record")
+    };
+    let mut hash = record.hash;
+    hash.data.truncate(hash.data.len() - 22);
+    hash.data_len = hash.data.len();
+    assert!(!hash.verify("openwall").unwrap());
+    hash.data.resize(convert::MAX_SYMMETRIC_DATA_LEN, 0);
+    hash.data_len = hash.data.len();
+    assert!(hash.verify("openwall").unwrap());
+
+    // usage 255 with exact validation against the public key
+    let mut hash = parse_hash(JOHN_HASHES[12].0).unwrap();
+    assert!(hash.extra_data.is_some());
+    assert!(hash.verify("openwall").unwrap());
+    if let Some(hash::ExtraData::Rsa { p }) = &mut hash.extra_data {
+        p[0] ^= 1;
+    }
+    assert!(!hash.verify("openwall").unwrap());
+
+    hash.cipher_algorithm = hash::CipherAlgorithm::Unknown;
+    assert!(matches!(
+        hash.verify("openwall"),
+        Err(Error::UnsupportedAlgorithm(_))
+    ));
+}
+
+#[test]
+#[cfg(feature = "crack")]
+fn test_cracker() {
+    use crack::{potfile_line, wordlist, Cracker};
+
+    let mut cracker = Cracker::new().threads(2);
+    // 3DES without MDC and CAST5 with MDC, both with a low count
+    for i in [23, 25] {
+        assert!(cracker.add(parse_hash(JOHN_HASHES[i].0).unwrap()).unwrap());
+    }
+    assert!(!cracker.add(parse_hash(JOHN_HASHES[23].0).unwrap()).unwrap());
+    let mut hash = parse_hash(JOHN_HASHES[0].0).unwrap();
+    hash.hash_algorithm = hash::HashAlgorithm::Unknown;
+    assert!(cracker.add(hash).is_err());
+
+    let words = "a\r\nb\nqwertyzxcvb12345\r\nc\n".repeat(4);
+    let mut found = Vec::new();
+    let summary = cracker
+        .run(wordlist(words.as_bytes()), |hash, password, _| {
+            found.push(potfile_line(hash, password))
+        })
+        .unwrap();
+    assert_eq!(summary.cracked, 2);
+    assert!(summary.tested >= 3);
+    found.sort();
+    let mut expected: Vec<_> = [23, 25]
+        .iter()
+        .map(|&i| format!("{}:{}", JOHN_HASHES[i].0, JOHN_HASHES[i].1))
+        .collect();
+    expected.sort();
+    assert_eq!(found, expected);
+
+    let hash = parse_hash(JOHN_HASHES[23].0).unwrap();
+    assert!(potfile_line(&hash, b"\xe4\xf6").ends_with(":$HEX[e4f6]"));
+}
+
+#[test]
+#[cfg(feature = "crack")]
+fn test_session() {
+    use crack::{Attack, Cracker, Mask, Session, WordlistFile};
+    use std::{
+        sync::{atomic::AtomicBool, Arc, Mutex},
+        time::Duration,
+    };
+
+    let dir = std::env::temp_dir().join(format!("pgp2hc-session-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let words_path = dir.join("words.txt");
+    std::fs::write(&words_path, "alpha\nbeta\ngamma\n").unwrap();
+    let words = WordlistFile::open(&words_path).unwrap();
+    let masks = Mask::parse("?d?d", &[]).unwrap().increments(1, 2);
+    let attacks = [
+        Attack::Wordlist(words.clone()),
+        Attack::Mask(masks.clone()),
+        Attack::HybridWordlistMask(words.clone(), masks.clone()),
+        Attack::HybridMaskWordlist(masks, words),
+        Attack::List(vec![b"x".to_vec(), b"y".to_vec()]),
+    ];
+    for attack in &attacks {
+        let all: Vec<_> = attack.candidates().unwrap().map(Result::unwrap).collect();
+        assert_eq!(all.len() as u64, attack.keyspace().unwrap());
+        for position in [0, 1, 9, 10, 109, 110, 111, 329, 330, 400] {
+            let resumed: Vec<_> = attack
+                .candidates_from(position)
+                .unwrap()
+                .map(Result::unwrap)
+                .collect();
+            assert_eq!(
+                resumed,
+                all[all.len().min(position as usize)..],
+                "{position}"
+            );
+        }
+    }
+
+    let mut cracker = Cracker::new().threads(2);
+    cracker.add(parse_hash(JOHN_HASHES[23].0).unwrap()).unwrap();
+    let checkpoints = Arc::new(Mutex::new(Vec::new()));
+    let saved = Arc::clone(&checkpoints);
+    let cracker = cracker.checkpoint(Duration::from_secs(3600), move |p| {
+        saved.lock().unwrap().push(p)
+    });
+    let attack = Attack::List(vec![b"a".to_vec(); 40]);
+    let summary = cracker
+        .run(attack.candidates().unwrap(), |_, _, _| ())
+        .unwrap();
+    assert_eq!((summary.tested, summary.position), (40, 40));
+    assert_eq!(*checkpoints.lock().unwrap(), [40]);
+    let cracker = cracker.stop_on(Arc::new(AtomicBool::new(true)));
+    let summary = cracker
+        .run(attack.candidates().unwrap(), |_, _, _| ())
+        .unwrap();
+    assert_eq!((summary.tested, summary.position), (0, 0));
+
+    let path = dir.join("test.session");
+    let _ = std::fs::remove_file(&path);
+    let hash = parse_hash(JOHN_HASHES[23].0).unwrap();
+    let mut session = Session::open(&path, "mask ?d?d").unwrap();
+    assert_eq!(session.position(), 0);
+    assert!(!session.is_cracked(&hash));
+    session.set_position(1234);
+    session.add_cracked(&hash, JOHN_HASHES[23].1.as_bytes());
+    session.save().unwrap();
+    let session = Session::open(&path, "mask ?d?d").unwrap();
+    assert_eq!(session.position(), 1234);
+    assert!(session.is_cracked(&hash));
+    assert!(!session.is_cracked(&parse_hash(JOHN_HASHES[24].0).unwrap()));
+    assert!(matches!(
+        Session::open(&path, "mask ?d?d?d"),
+        Err(Error::InvalidSession(_))
+    ));
+    assert!(matches!(
+        Session::open(&words_path, "mask ?d?d"),
+        Err(Error::InvalidSession(_))
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+#[cfg(feature = "crack")]
+fn test_node_slices() {
+    use crack::{Attack, Mask, Rule, WordlistFile};
+
+    let path = std::env::temp_dir().join(format!("pgp2hc-nodes-{}.txt", std::process::id()));
+    let words: Vec<_> = (0..11).map(|i| format!("word{i}")).collect();
+    std::fs::write(&path, words.join("\n")).unwrap();
+    let words = WordlistFile::open(&path).unwrap();
+    let rules = ["c", "$1 $2", "<4"].map(|r| Rule::parse(r).unwrap());
+    let attacks = [
+        Attack::Wordlist(words.clone()),
+        Attack::WordlistRules(words, rules.to_vec()),
+        Attack::Mask(Mask::parse("?d?l", &[]).unwrap().increments(1, 2)),
+    ];
+    for attack in &attacks {
+        let all: Vec<_> = attack
+            .positions_from(0)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(all.len() as u64, attack.keyspace().unwrap());
+        for nodes in [1, 2, 3, 7, 1000] {
+            let mut union = Vec::new();
+            let mut end = 0;
+            for node in 1..=nodes {
+                let range = attack.node_range(node, nodes).unwrap();
+                // contiguous and non-overlapping
+                assert_eq!(range.start, end);
+                end = range.end;
+                let slice: Vec<_> = attack
+                    .positions_from(range.start)
+                    .unwrap()
+                    .take((range.end - range.start) as usize)
+                    .map(Result::unwrap)
+                    .collect();
+                // balanced, even where a rule rejects every word
+                assert_eq!(slice.len() as u64, range.end - range.start);
+                union.extend(slice);
+            }
+            assert_eq!(end, attack.keyspace().unwrap());
+            assert_eq!(union, all, "{nodes} nodes");
+        }
+    }
+    // "<4" rejects all words, but each keeps its position
+    let rules = &attacks[1];
+    let all: Vec<_> = rules
+        .positions_from(0)
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(
+        all[..3],
+        [Some(b"Word0".to_vec()), Some(b"word012".to_vec()), None]
+    );
+    assert_eq!(rules.candidates().unwrap().count(), 22);
+    for position in [0, 1, 2, 3, 17, 32, 33] {
+        let resumed: Vec<_> = rules
+            .candidates_from(position)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        let expected: Vec<_> = all[position as usize..].iter().flatten().cloned().collect();
+        assert_eq!(resumed, expected, "{position}");
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "crack")]
+fn test_mask() {
+    use crack::{Attack, Cracker, Mask, WordlistFile};
+
+    let mask = Mask::parse("a?d?1", &["xy?u"]).unwrap();
+    assert_eq!(mask.len(), 3);
+    assert_eq!(mask.keyspace(), Some(10 * 28));
+    let all: Vec<_> = mask.iter_from(0).collect();
+    assert_eq!(all.len(), 280);
+    assert_eq!(all[0], b"a0x");
+    assert_eq!(all[1], b"a0y");
+    assert_eq!(all[2], b"a0A");
+    assert_eq!(all[279], b"a9Z");
+    for (i, c) in all.iter().enumerate() {
+        assert_eq!(&mask.candidate(i as u64), c);
+    }
+    assert_eq!(mask.iter_from(100).collect::<Vec<_>>(), all[100..]);
+    assert_eq!(mask.iter_from(280).count(), 0);
+
+    assert_eq!(Mask::parse("??", &[]).unwrap().candidate(0), b"?");
+    assert_eq!(Mask::parse("?a", &[]).unwrap().keyspace(), Some(95));
+    assert_eq!(Mask::parse("?b?b", &[]).unwrap().keyspace(), Some(65536));
+    assert_eq!(Mask::parse(&"?b".repeat(9), &[]).unwrap().keyspace(), None);
+    assert!(Mask::parse("?1", &[]).is_err());
+    for bad in ["", "?", "?x", "?2"] {
+        assert!(
+            matches!(Mask::parse(bad, &["a"]), Err(Error::InvalidMask(_))),
+            "{bad}"
+        );
+    }
+
+    let increments = Mask::parse("?d?l?u", &[]).unwrap().increments(2, 5);
+    let lengths: Vec<_> = increments.iter().map(|m| m.len()).collect();
+    assert_eq!(lengths, [2, 3]);
+    assert_eq!(Attack::Mask(increments).keyspace(), Some(260 + 260 * 26));
+
+    let path = std::env::temp_dir().join(format!("pgp2hc-mask-{}.txt", std::process::id()));
+    std::fs::write(&path, "foo\nbar\r\n").unwrap();
+    let words = WordlistFile::open(&path).unwrap();
+    let mask = Mask::parse("?d", &[]).unwrap();
+    let attack = Attack::HybridWordlistMask(words.clone(), vec![mask.clone()]);
+    let candidates: Vec<_> = attack.candidates().unwrap().map(|c| c.unwrap()).collect();
+    assert_eq!(attack.keyspace(), Some(20));
+    assert_eq!(candidates.len(), 20);
+    assert_eq!(candidates[0], b"foo0");
+    assert_eq!(candidates[19], b"bar9");
+    let attack = Attack::HybridMaskWordlist(vec![mask], words);
+    let candidates: Vec<_> = attack.candidates().unwrap().map(|c| c.unwrap()).collect();
+    assert_eq!(candidates[11], b"1bar");
+    std::fs::remove_file(&path).unwrap();
+
+    let mut cracker = Cracker::new();
+    cracker.add(parse_hash(JOHN_HASHES[24].0).unwrap()).unwrap();
+    let attack = Attack::Mask(vec![Mask::parse("qwerty?lxcvb1234?d", &[]).unwrap()]);
+    let summary = cracker
+        .run(attack.candidates().unwrap(), |_, password, _| {
+            assert_eq!(password, b"qwertyzxcvb12345")
+        })
+        .unwrap();
+    assert_eq!(summary.cracked, 1);
+}
+
+#[test]
+#[cfg(feature = "crack")]
+fn test_rules() {
+    use crack::{load_rules, Rule};
+
+    // examples from the hashcat wiki, https://hashcat.net/wiki/doku.php?id=rule_based_attack
+    let examples = [
+        (":", "p@ssW0rd"),
+        ("l", "p@ssw0rd"),
+        ("u", "P@SSW0RD"),
+        ("c", "P@ssw0rd"),
+        ("C", "p@SSW0RD"),
+        ("t", "P@SSw0RD"),
+        ("T3", "p@sSW0rd"),
+        ("r", "dr0Wss@p"),
+        ("d", "p@ssW0rdp@ssW0rd"),
+        ("p2", "p@ssW0rdp@ssW0rdp@ssW0rd"),
+        ("f", "p@ssW0rddr0Wss@p"),
+        ("{", "@ssW0rdp"),
+        ("}", "dp@ssW0r"),
+        ("$1$2", "p@ssW0rd12"),
+        ("^2^1", "12p@ssW0rd"),
+        ("[", "@ssW0rd"),
+        ("]", "p@ssW0r"),
+        ("D3", "p@sW0rd"),
+        ("x04", "p@ss"),
+        ("O12", "psW0rd"),
+        ("i4!", "p@ss!W0rd"),
+        ("o3$", "p@s$W0rd"),
+        ("'6", "p@ssW0"),
+        ("ss$", "p@$$W0rd"),
+        ("@s", "p@W0rd"),
+        ("z2", "ppp@ssW0rd"),
+        ("Z2", "p@ssW0rddd"),
+        ("q", "pp@@ssssWW00rrdd"),
+        ("k", "@pssW0rd"),
+        ("K", "p@ssW0dr"),
+        ("*34", "p@sWs0rd"),
+        ("+2", "p@tsW0rd"),
+        ("-1", "p?ssW0rd"),
+        (".1", "psssW0rd"),
+        (",1", "ppssW0rd"),
+        ("y2", "p@p@ssW0rd"),
+        ("Y2", "p@ssW0rdrd"),
+    ];
+    for (rule, expected) in examples {
+        let candidate = Rule::parse(rule).unwrap().apply(b"p@ssW0rd");
+        assert_eq!(candidate.as_deref(), Some(expected.as_bytes()), "{rule}");
+    }
+    let title = Rule::parse("E").unwrap().apply(b"p@ssW0rd w0rld");
+    assert_eq!(title.unwrap(), b"P@ssw0rd W0rld");
+    let title = Rule::parse("e-").unwrap().apply(b"p@ssW0rd-w0rld");
+    assert_eq!(title.unwrap(), b"P@ssw0rd-W0rld");
+
+    // leetspeak, spaces between functions, a space as argument
+    let rule = Rule::parse("c sa@ so0 $ $1").unwrap();
+    assert_eq!(rule.apply(b"password").unwrap(), b"P@ssw0rd 1");
+    // positions beyond the word leave it unchanged
+    for rule in ["D9", "T9", "x38", "i9x", "o9x", "*09"] {
+        assert_eq!(Rule::parse(rule).unwrap().apply(b"abc").unwrap(), b"abc");
+    }
+    // rejections
+    let accepted = |rule: &str| Rule::parse(rule).unwrap().apply(b"p@ssW0rd").is_some();
+    assert!(accepted("<8") && !accepted("<7"));
+    assert!(accepted(">8") && !accepted(">9"));
+    assert!(accepted("_8") && !accepted("_7"));
+    assert!(accepted("!x") && !accepted("!@"));
+    assert!(accepted("/@") && !accepted("/x"));
+    assert!(accepted("(p") && !accepted("(@"));
+    assert!(accepted(")d") && !accepted(")r"));
+    assert!(accepted("=1@") && !accepted("=1p"));
+    // candidates longer than 256 bytes are rejected, even if they get shorter again
+    let word = [b'a'; 128];
+    assert_eq!(Rule::parse("d").unwrap().apply(&word).unwrap().len(), 256);
+    assert_eq!(Rule::parse("d $1").unwrap().apply(&word), None);
+    assert_eq!(Rule::parse("d $1 ]").unwrap().apply(&word), None);
+    // and so are longer words, whatever the rule
+    let word = [b'a'; 257];
+    for rule in ["", ":", "l", "]", ">1"] {
+        assert_eq!(Rule::parse(rule).unwrap().apply(&word), None, "{rule}");
+    }
+    assert!(Rule::parse(":").unwrap().apply(&word[..256]).is_some());
+
+    for bad in ["$", "T", "Tz", "x1", "?"] {
+        assert!(
+            matches!(Rule::parse(bad), Err(Error::InvalidRule(_))),
+            "{bad}"
+        );
+    }
+    for unsupported in ["L1", "R1", "31-", "M", "4", "6", "X123", "Q", "%2a"] {
+        assert!(
+            matches!(Rule::parse(unsupported), Err(Error::UnsupportedRule(_))),
+            "{unsupported}"
+        );
+    }
+    // rules with unsupported functions are skipped, like hashcat does
+    let file = "# best rules\n:\n\nc $1\r\n3NX\nsa@\n%2a\n";
+    assert_eq!(load_rules(file.as_bytes()).unwrap().len(), 3);
+    let err = load_rules("l\nTz\n".as_bytes()).unwrap_err();
+    assert!(err.to_string().contains("line 2"), "{err}");
+}
+
+#[test]
+#[cfg(feature = "crack")]
+fn test_typos() {
+    use crack::{typo_variants, Attack, Cracker, KeyboardLayout};
+
+    let sorted = |mut v: Vec<char>| {
+        v.sort();
+        v
+    };
+    let qwerty = KeyboardLayout::qwerty();
+    assert_eq!(
+        sorted(qwerty.neighbours('s')),
+        ['a', 'd', 'e', 'w', 'x', 'z']
+    );
+    assert_eq!(
+        sorted(qwerty.neighbours('S')),
+        ['A', 'D', 'E', 'W', 'X', 'Z']
+    );
+    assert_eq!(sorted(qwerty.neighbours('1')), ['2', 'q']);
+    let qwertz = KeyboardLayout::by_name("qwertz").unwrap();
+    assert_eq!(
+        sorted(qwertz.neighbours('z')),
+        ['6', '7', 'g', 'h', 't', 'u']
+    );
+    assert!(qwertz.neighbours('€').is_empty());
+    let custom = KeyboardLayout::parse("abc\ndef").unwrap();
+    assert_eq!(sorted(custom.neighbours('e')), ['b', 'c', 'd', 'f']);
+    assert!(matches!(
+        KeyboardLayout::parse("abc ABCD"),
+        Err(Error::InvalidLayout(_))
+    ));
+
+    let variants = typo_variants(&["Secret"], &qwerty, 1);
+    let position = |v: &str| variants.iter().position(|x| x == v).unwrap();
+    assert_eq!(variants[0], "Secret");
+    assert_eq!(variants[1], "sECRET");
+    assert!(position("eScret") < position("Scret"));
+    assert!(position("Scret") < position("Seecret"));
+    assert!(position("Seecret") < position("Sexret"));
+    assert!(position("Sexret") < position("Secrewt"));
+    assert!(position("Secrewt") < position("SecRet"));
+    assert!(position("SecRet") < position("Sec#ret"));
+    let unique: std::collections::HashSet<_> = variants.iter().collect();
+    assert_eq!(unique.len(), variants.len());
+
+    let deeper = typo_variants(&["Secret"], &qwerty, 2);
+    assert_eq!(deeper[..variants.len()], variants[..]);
+    assert!(deeper.contains(&"sECRTE".to_string()));
+
+    let mut cracker = Cracker::new();
+    cracker.add(parse_hash(JOHN_HASHES[24].0).unwrap()).unwrap();
+    let variants = typo_variants(&["qwertyzxcvb12354", "letmein"], &qwerty, 1);
+    let attack = Attack::List(variants.into_iter().map(String::into_bytes).collect());
+    let summary = cracker
+        .run(attack.candidates().unwrap(), |_, password, _| {
+            assert_eq!(password, b"qwertyzxcvb12345")
+        })
+        .unwrap();
+    assert_eq!(summary.cracked, 1);
+}
+
+#[test]
+#[cfg(feature = "encoding")]
+fn test_encodings() {
+    use encoding::encode_variants;
+
+    let text = "ä€".as_bytes();
+    assert_eq!(Encoding::Latin1.encode(text), None);
+    assert_eq!(Encoding::Cp1252.encode(text), Some(vec![0xe4, 0x80]));
+    assert_eq!(Encoding::Latin1.encode("ä".as_bytes()), Some(vec![0xe4]));
+    assert_eq!(
+        Encoding::Nfd.encode("ä".as_bytes()),
+        Some(b"a\xcc\x88".to_vec())
+    );
+    assert_eq!(
+        Encoding::Nfc.encode(b"a\xcc\x88"),
+        Some("ä".as_bytes().to_vec())
+    );
+    assert_eq!(Encoding::Nfc.encode(b"\xff"), None);
+    assert_eq!(Encoding::Utf8.encode(b"\xff"), Some(vec![0xff]));
+    assert_eq!(encode_variants(b"secret", &Encoding::ALL).len(), 1);
+    let variants = encode_variants("pässwört".as_bytes(), &Encoding::ALL);
+    let found: Vec<_> = variants.iter().map(|(e, _)| *e).collect();
+    assert_eq!(found, [Encoding::Utf8, Encoding::Nfd, Encoding::Latin1]);
+
+    /* gpg -c --s2k-mode 1 --s2k-digest-algo SHA256 --cipher-algo AES with the passphrase in Latin-1, CP1252 and NFD */
+    let hashes = [
+        ("$gpg$*0*60*16669b1ba5cf92f642f026b3b3b414d3cfb0b5c5ff55e4dae0b171c192ec0d5a09b2a1a102abd31ac123d706ee88caf87c6c2fe701f14e1a5fb23469*1*18*8*7*0*bdc2c041c19228a2", "pässwört", Encoding::Latin1),
+        ("$gpg$*0*60*8e0e017c36d991fcb2f8d953bbbc886b0e4280e22632e3bc38161646e1a189a5bc0706aebf69c9a71b97373c3f34a1da22fce61ceb47817aa1d1ee86*1*18*8*7*0*aa323c50ee3bcca6", "€uro", Encoding::Cp1252),
+        ("$gpg$*0*60*378177115ebcf9e094077b954c6f75dec0996f8c1176cc7cb0aa5ffa3603f7e085db91d2539b98bea2bb8c14677da30c07e26d1348ca1ec2c3659b48*1*18*8*7*0*777f72c99989f0ed", "pässwört", Encoding::Nfd),
+    ];
+    for (hash, password, encoding) in hashes {
+        let hash = parse_hash(hash).unwrap();
+        assert!(!hash.verify(password).unwrap());
+        assert_eq!(
+            hash.verify_encodings(password, &Encoding::ALL).unwrap(),
+            Some(encoding)
+        );
+        assert_eq!(
+            hash.verify_encodings(password, &[Encoding::Utf8]).unwrap(),
+            None
+        );
+    }
+}
+
+#[test]
+fn test_potfile() {
+    use potfile::{decode_password, potfile_line};
+
+    let key1 = parse_hash(JOHN_HASHES[23].0).unwrap();
+    let key2 = parse_hash(JOHN_HASHES[24].0).unwrap();
+    let key3 = parse_hash(JOHN_HASHES[25].0).unwrap();
+    // john keeps the first 16 bytes of the SHA-1 digest of the full hash
+    let shortened = |hash: &PgpHash| {
+        let digest = <sha1::Sha1 as sha1::Digest>::digest(hash.to_string());
+        format!(
+            "{}$SOURCE_HASH${}",
+            &hash.to_string()[..40],
+            hex::encode(&digest[..16])
+        )
+    };
+    // the same prefix as key2, but the digest of another hash
+    let other = shortened(&parse_hash(JOHN_HASHES[0].0).unwrap());
+    let other = format!("{}{}", &JOHN_HASHES[24].0[..40], &other[40..]);
+    let text = format!(
+        "{}\r\n$dynamic_0$abc:x\n{other}:wrong\n{}:pass:word\nnot a hash\n{}\n",
+        potfile_line(&key1, b"\xe4\n"),
+        shortened(&key2),
+        potfile_line(&key3, JOHN_HASHES[25].1.as_bytes()),
+    );
+    let potfile = Potfile::read(text.as_bytes()).unwrap();
+    assert_eq!(potfile.len(), 4);
+    assert_eq!(potfile.get(&key1), Some(&b"\xe4\n"[..]));
+    assert_eq!(potfile.get(&key2), Some(&b"pass:word"[..]));
+    assert_eq!(potfile.get(&key3), Some(JOHN_HASHES[25].1.as_bytes()));
+    assert_eq!(potfile.get(&parse_hash(JOHN_HASHES[0].0).unwrap()), None);
+    assert_eq!(decode_password(b"$HEX[616263]"), b"abc");
+    assert_eq!(decode_password(b"$HEX[zz]"), b"$HEX[zz]");
+
+    let records: Vec<_> = Extractor::new()
+        .extract_file("data/testkey1.key")
+        .unwrap()
+        .filter_map(|item| match item {
+            Extracted::Record(r) => Some(r),
+            Extracted::Warning(_) => None,
+        })
+        .collect();
+    let password = std::fs::read_to_string("data/testkey1.pw").unwrap();
+    let mut potfile = Potfile::new();
+    potfile.insert(&records[1].hash, password.trim().as_bytes());
+    let marked: Vec<_> = Extractor::new()
+        .potfile(potfile.clone())
+        .extract_file("data/testkey1.key")
+        .unwrap()
+        .filter_map(|item| match item {
+            Extracted::Record(r) => Some(r.password),
+            Extracted::Warning(_) => None,
+        })
+        .collect();
+    assert_eq!(marked, [None, Some(password.trim().as_bytes().to_vec())]);
+    let left = Extractor::new()
+        .potfile(potfile)
+        .filter(|r| r.password.is_none())
+        .extract_file("data/testkey1.key")
+        .unwrap()
+        .count();
+    assert_eq!(left, 1);
+}
+
+#[test]
+#[cfg(feature = "personal")]
+fn test_personal() {
+    let mut hints = Hints::new();
+    hints.add_user(&UserInfo::from(
+        "Alice Smith (work) <alice.smith84@example.com>",
+    ));
+    hints.add_created("2023-05-01T00:00:00Z".parse().unwrap());
+    hints.add_word("Rex");
+    let candidates = hints.candidates();
+    let position = |c: &str| {
+        candidates
+            .iter()
+            .position(|x| x == c)
+            .unwrap_or_else(|| panic!("{c} missing"))
+    };
+    for c in [
+        "AliceSmith",
+        "smith.alice",
+        "asmith",
+        "AliceS",
+        "as",
+        "work",
+        "example",
+        "alice84",
+        "Rex2020",
+        "REX24",
+        "Smith2021!",
+        "2024",
+    ] {
+        position(c);
+    }
+    assert!(position("alice") < position("alice2023"));
+    assert!(position("alice2023") < position("alice123"));
+    assert!(!candidates.contains(&"alice2025".to_string()));
+    let unique: std::collections::HashSet<_> = candidates.iter().collect();
+    assert_eq!(unique.len(), candidates.len());
+    assert!(Hints::new().candidates().is_empty());
+}
+
+#[test]
+#[cfg(all(feature = "crack", feature = "personal"))]
+fn test_personal_crack() {
+    use crack::{Attack, Cracker};
+
+    /* gpg -c --s2k-mode 1 --s2k-digest-algo SHA256 --cipher-algo AES, passphrase "Smith2021!" */
+    let hash = "$gpg$*0*60*8fc74f97ea0b18236e66822bd320cea30a8ce7cfd611ed692919aacecee92312ba8d2f015057eda2b2b20d1339b69389cd957b0b44135e820e18b643*1*18*8*7*0*da3f2005780c42a4";
+    let mut cracker = Cracker::new();
+    cracker.add(parse_hash(hash).unwrap()).unwrap();
+    let mut hints = Hints::new();
+    hints.add_user(&UserInfo::from("Alice Smith <alice@example.com>"));
+    hints.add_year(2021);
+    let candidates = hints.candidates();
+    let attack = Attack::List(candidates.into_iter().map(String::into_bytes).collect());
+    let summary = cracker
+        .run(attack.candidates().unwrap(), |_, password, _| {
+            assert_eq!(password, b"Smith2021!")
+        })
+        .unwrap();
+    assert_eq!(summary.cracked, 1);
+}
+
+#[test]
+#[cfg(feature = "protect")]
+fn test_reprotect() {
+    use protect::read_secret_keys;
+
+    let password = std::fs::read_to_string("data/testkey1.pw").unwrap();
+    let password = password.trim();
+    let keys = read_secret_keys(&std::fs::read("data/testkey1.key").unwrap()).unwrap();
+    assert_eq!(keys.len(), 1);
+    let key = &keys[0];
+    // the trust packets of the GnuPG 1 keyring must not end the key
+    assert_eq!(key.details.users.len(), 1);
+    assert_eq!(key.secret_subkeys.len(), 1);
+
+    let protection = Protection {
+        cipher: SymmetricKeyAlgorithm::AES256,
+        hash: HashAlgorithm::SHA2_512,
+        count: 0x60,
+    };
+    assert!(matches!(
+        reprotect(key, "wrong", "new", &protection),
+        Err(Error::WrongPassphrase(_))
+    ));
+    let reprotected = reprotect(key, password, "new passphrase", &protection).unwrap();
+
+    for bytes in [
+        reprotected.to_bytes().unwrap(),
+        reprotected.to_armored_bytes(None).unwrap(),
+    ] {
+        let exported = read_secret_keys(&bytes).unwrap().remove(0);
+        assert_eq!(exported.public_key(), key.public_key());
+        assert_eq!(exported.details, key.details);
+        exported
+            .unlock(|| "new passphrase".into(), |_| Ok(()))
+            .unwrap();
+        let subkey = &exported.secret_subkeys[0];
+        assert_eq!(subkey.signatures, key.secret_subkeys[0].signatures);
+        for hash in [
+            PgpHash::try_from(&exported).unwrap(),
+            PgpHash::try_from(subkey).unwrap(),
+        ] {
+            assert_eq!(hash.usage, Usage::TwoFiveFour);
+            assert_eq!(hash.cipher_algorithm, hash::CipherAlgorithm::AES256);
+            assert_eq!(hash.hash_algorithm, hash::HashAlgorithm::SHA512);
+            assert!(hash.verify("new passphrase").unwrap());
+            assert!(!hash.verify(password).unwrap());
+        }
+    }
+
+    // an existing output file is only overwritten with --force, and only its owner can read it
+    #[cfg(feature = "cli")]
+    {
+        use clap::Parser;
+        #[cfg(unix)]
+        use std::os::unix::fs::PermissionsExt;
+
+        let output = env::temp_dir().join(format!("pgp2hc-export-{}.gpg", std::process::id()));
+        std::fs::write(&output, "existing").unwrap();
+        #[cfg(unix)]
+        std::fs::set_permissions(&output, PermissionsExt::from_mode(0o644)).unwrap();
+        let args = |force: bool| {
+            let mut argv = vec!["pgp2hc", "export", "data/testkey1.key", "-p", password];
+            argv.extend(["-n", "new", "--s2k-count", "1024", "-o"]);
+            argv.push(output.to_str().unwrap());
+            if force {
+                argv.push("--force");
+            }
+            match Cli::parse_from(argv).command {
+                Some(cli::Command::Export(args)) => args,
+                _ => unreachable!(),
+            }
+        };
+        let err = cli::export(&args(false)).unwrap_err();
+        assert!(matches!(&err, Error::Io(e) if e.kind() == std::io::ErrorKind::AlreadyExists));
+        assert_eq!(std::fs::read(&output).unwrap(), b"existing");
+        assert_eq!(cli::export(&args(true)).unwrap(), 1);
+        assert_eq!(
+            read_secret_keys(&std::fs::read(&output).unwrap())
+                .unwrap()
+                .len(),
+            1
+        );
+        #[cfg(unix)]
+        {
+            let mode = std::fs::metadata(&output).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        std::fs::remove_file(&output).unwrap();
+    }
+}
+
+#[test]
+#[cfg(feature = "protect")]
+fn test_reprotect_data() {
+    use protect::{coded_count, reprotect_data, RawPacket};
+
+    assert_eq!(coded_count(65536), 0x60);
+    assert_eq!(coded_count(65537), 0x61);
+    assert_eq!(coded_count(usize::MAX), 0xff);
+
+    let armored = std::fs::read("data/testkey1.key").unwrap();
+    let data = extract::dearmor(&armored).unwrap();
+    let password = std::fs::read_to_string("data/testkey1.pw").unwrap();
+    let password = password.trim();
+    let protection = Protection {
+        cipher: SymmetricKeyAlgorithm::AES128,
+        hash: HashAlgorithm::SHA2_512,
+        count: coded_count(100_000),
+    };
+    assert!(matches!(
+        reprotect_data(&data, "wrong", "wrong", &protection),
+        Err(Error::WrongPassphrase(_))
+    ));
+    let (reprotected, count) = reprotect_data(&data, password, password, &protection).unwrap();
+    assert_eq!(count, 2);
+
+    // everything but the secret key material is unchanged, including the trust packets
+    let split = |mut data: &[u8]| {
+        let mut packets = Vec::new();
+        while !data.is_empty() {
+            let packet = RawPacket::split(&mut data).unwrap();
+            packets.push((
+                packet.tag,
+                packet.raw.to_vec(),
+                packet.body.unwrap().to_vec(),
+            ));
+        }
+        packets
+    };
+    let (before, after) = (split(&data), split(&reprotected));
+    assert_eq!(before.len(), 9);
+    assert_eq!(before.len(), after.len());
+    let keys = protect::read_secret_keys(&reprotected).unwrap().remove(0);
+    let publics = [
+        keys.primary_key.public_key().to_bytes().unwrap(),
+        keys.secret_subkeys[0].key.public_key().to_bytes().unwrap(),
+    ];
+    let mut publics = publics.iter();
+    for ((tag, raw, body), (new_tag, new_raw, new_body)) in before.iter().zip(&after) {
+        assert_eq!(tag, new_tag);
+        match tag {
+            5 | 7 => {
+                let public = publics.next().unwrap();
+                assert!(body.starts_with(public) && new_body.starts_with(public));
+                assert_ne!(body, new_body);
+            }
+            _ => assert_eq!(raw, new_raw),
+        }
+    }
+
+    for record in Extractor::new().extract_bytes(&reprotected).unwrap() {
+        let Extracted::Record(record) = record else {
+            panic!("unexpected warning");
+        };
+        assert_eq!(record.hash.cipher_algorithm, hash::CipherAlgorithm::AES128);
+        assert_eq!(record.hash.hash_algorithm, hash::HashAlgorithm::SHA512);
+        assert_eq!(record.hash.count, Some(102_400));
+        assert!(record.hash.verify(password).unwrap());
+    }
+
+    // an existing backup is only overwritten with --force
+    #[cfg(feature = "cli")]
+    {
+        use clap::Parser;
+        #[cfg(unix)]
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = env::temp_dir().join(format!("pgp2hc-reprotect-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("key.asc");
+        let backup = dir.join("key.asc.bak");
+        std::fs::write(&path, &armored).unwrap();
+        std::fs::write(&backup, "older backup").unwrap();
+        #[cfg(unix)]
+        std::fs::set_permissions(&path, PermissionsExt::from_mode(0o600)).unwrap();
+        let args = |force: bool| {
+            let path = path.to_str().unwrap();
+            let mut argv = vec!["pgp2hc", "reprotect", path, "-p", password];
+            argv.extend(["--s2k-count", "1024"]);
+            if force {
+                argv.push("--force");
+            }
+            match Cli::parse_from(argv).command {
+                Some(cli::Command::Reprotect(args)) => args,
+                _ => unreachable!(),
+            }
+        };
+        let err = cli::reprotect(&args(false)).unwrap_err();
+        assert!(matches!(&err, Error::Io(e) if e.kind() == std::io::ErrorKind::AlreadyExists));
+        assert_eq!(std::fs::read(&path).unwrap(), armored);
+        assert_eq!(cli::reprotect(&args(true)).unwrap(), 2);
+        assert_eq!(std::fs::read(&backup).unwrap(), armored);
+        assert_ne!(std::fs::read(&path).unwrap(), armored);
+        // neither the new keys nor the backup are readable by others
+        #[cfg(unix)]
+        for file in [&path, &backup] {
+            let mode = std::fs::metadata(file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600, "{}", file.display());
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[test]
+#[cfg(feature = "decrypt")]
+fn test_decrypt() {
+    for (file, protected) in [("symmetric1", true), ("symmetric2", false)] {
+        let data = std::fs::read(format!("data/{file}.gpg")).unwrap();
+        let password = std::fs::read_to_string(format!("data/{file}.pw")).unwrap();
+        let message = decrypt_message(&data, password.trim().as_bytes()).unwrap();
+        assert_eq!(message.integrity_protected, protected);
+        assert_eq!(message.literals.len(), 1);
+        assert_eq!(message.literals[0].file_name, b"secret.txt");
+        assert_eq!(message.literals[0].data, b"hello symmetric world\n");
+        assert!(matches!(
+            decrypt_message(&data, b"wrong"),
+            Err(Error::WrongPassphrase(_))
+        ));
+    }
+
+    // an SKESK with an encrypted session key can't be verified, it is skipped
+    let data = std::fs::read("data/symmetric1.gpg").unwrap();
+    let (skesk, rest) = data.split_at(15);
+    let mut unusable = vec![0x8c, 0x0d + 16];
+    unusable.extend_from_slice(&skesk[2..]);
+    unusable.extend_from_slice(&[0; 16]);
+    let message = decrypt_message(&[&unusable[..], &data].concat(), b"openwall").unwrap();
+    assert_eq!(message.literals[0].data, b"hello symmetric world\n");
+    assert!(matches!(
+        decrypt_message(&[&unusable[..], rest].concat(), b"openwall"),
+        Err(Error::UnsupportedS2k(_))
+    ));
+    assert!(matches!(
+        decrypt_message(rest, b"openwall"),
+        Err(Error::InvalidMessage(_))
+    ));
+
+    // a modified MDC
+    let mut data = std::fs::read("data/symmetric1.gpg").unwrap();
+    *data.last_mut().unwrap() ^= 1;
+    assert!(matches!(
+        decrypt_message(&data, b"openwall"),
+        Err(Error::Integrity(_))
+    ));
+
+    // the plaintext is only readable by its owner, even if the file existed
+    #[cfg(all(feature = "cli", unix))]
+    {
+        use clap::Parser;
+        use std::os::unix::fs::PermissionsExt;
+
+        let output = env::temp_dir().join(format!("pgp2hc-decrypt-{}.txt", std::process::id()));
+        std::fs::write(&output, "existing").unwrap();
+        std::fs::set_permissions(&output, PermissionsExt::from_mode(0o644)).unwrap();
+        let argv = [
+            "pgp2hc",
+            "decrypt",
+            "data/symmetric1.gpg",
+            "-p",
+            "openwall",
+            "-f",
+            "-o",
+        ];
+        let args = match Cli::parse_from(argv.iter().chain([&output.to_str().unwrap()])).command {
+            Some(cli::Command::Decrypt(args)) => args,
+            _ => unreachable!(),
+        };
+        cli::decrypt(&args).unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), b"hello symmetric world\n");
+        let mode = std::fs::metadata(&output).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        std::fs::remove_file(&output).unwrap();
+    }
+}
+
+#[test]
+#[cfg(feature = "audit")]
+fn test_audit() {
+    use audit::Rule;
+
+    let rules = |report: &Report| -> Vec<Vec<Rule>> {
+        report
+            .keys
+            .iter()
+            .map(|k| k.findings.iter().map(|f| f.rule).collect())
+            .collect()
+    };
+    // testkey2: RSA 4096 with CAST5 and SHA-1
+    let policy = Policy::default();
+    let report = policy.audit_file("data/testkey2.key").unwrap();
+    let weak = vec![Rule::WeakDigest, Rule::WeakCipher];
+    assert_eq!(rules(&report), [weak.clone(), weak]);
+    assert_eq!(report.violations(), 4);
+    assert!(report.keys[1].is_subkey);
+    assert_eq!(report.keys[0].source.path, Some("data/testkey2.key".into()));
+
+    let mut data = generate_key(None).to_bytes().unwrap();
+    data.extend(generate_key(Some("test")).to_bytes().unwrap());
+    let report = policy.audit_bytes(&data).unwrap();
+    assert_eq!(rules(&report), [vec![Rule::Unprotected], vec![]]);
+    assert!(report.is_complete());
+
+    // corrupt or foreign data and public keys leave the audit incomplete
+    let keyfile = std::fs::read("data/testkey2.key").unwrap();
+    let mut corrupt = extract::dearmor(&keyfile).unwrap();
+    corrupt.truncate(corrupt.len() / 2);
+    let key = generate_key(Some("test"));
+    let public = key.public_key().sign(&key, || "test".into()).unwrap();
+    let public = public.to_bytes().unwrap();
+    for data in [corrupt, vec![0xff; 100], b"not a key".to_vec(), public] {
+        assert!(!policy.audit_bytes(&data).unwrap().is_complete());
+    }
+
+    // the stub of an offline primary key (gpg --export-secret-subkeys) is skipped
+    let report = policy.audit_file("data/stub.key").unwrap();
+    assert_eq!(report.skipped.len(), 1);
+    assert!(matches!(
+        report.skipped[0].error,
+        Error::NoSecretKeyMaterial(_)
+    ));
+    assert_eq!(report.keys.len(), 1);
+    assert!(report.keys[0].is_subkey);
+    assert!(report.warnings.is_empty());
+    assert!(report.is_complete());
+
+    let policy = Policy::from_toml(
+        "min_rsa_bits = 2048\nforbidden_ciphers = [\"3des\", 3]\nforbidden_usages = [255]",
+    )
+    .unwrap();
+    assert!(!policy.forbid_unprotected);
+    assert_eq!(
+        policy.forbidden_ciphers,
+        [
+            hash::CipherAlgorithm::TripleDES,
+            hash::CipherAlgorithm::CAST5
+        ]
+    );
+    assert_eq!(policy.audit_bytes(&data).unwrap().violations(), 0);
+    let report = policy.audit_file("data/testkey2.key").unwrap();
+    assert_eq!(rules(&report)[0], [Rule::WeakCipher]);
+    // testkey1: RSA 2048
+    let report = Policy::default().audit_file("data/testkey1.key").unwrap();
+    assert_eq!(rules(&report)[0], [Rule::WeakDigest, Rule::SmallRsaKey]);
+    assert_eq!(
+        policy.audit_file("data/testkey1.key").unwrap().violations(),
+        0
+    );
+
+    // the size of an unprotected key is checked even if unprotected keys are allowed
+    let key = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::Rsa(2048))
+        .can_create_certificates(true)
+        .primary_user_id("Test Key <test@example.com>".into())
+        .build()
+        .unwrap()
+        .generate()
+        .unwrap();
+    let data = key.sign(String::new).unwrap().to_bytes().unwrap();
+    let policy = Policy::from_toml("min_rsa_bits = 3072").unwrap();
+    assert_eq!(
+        rules(&policy.audit_bytes(&data).unwrap()),
+        [vec![Rule::SmallRsaKey]]
+    );
+    assert_eq!(
+        rules(&Policy::default().audit_bytes(&data).unwrap()),
+        [vec![Rule::Unprotected, Rule::SmallRsaKey]]
+    );
+
+    assert!(matches!(
+        Policy::from_toml("forbidden_digests = [\"sha3\"]"),
+        Err(Error::InvalidPolicy(_))
+    ));
+    assert!(matches!(
+        Policy::from_toml("min_bits = 4096"),
+        Err(Error::InvalidPolicy(_))
+    ));
+}
+
+#[test]
+#[cfg(feature = "audit")]
+fn test_audit_reports() {
+    let policy = Policy::default();
+    let reports = [
+        policy.audit_file("data/testkey1.key").unwrap(),
+        policy
+            .audit_bytes(&generate_key(None).to_bytes().unwrap())
+            .unwrap(),
+    ];
+
+    let mut sarif = Vec::new();
+    audit::write_sarif(&reports, &mut sarif).unwrap();
+    let sarif: serde_json::Value = serde_json::from_slice(&sarif).unwrap();
+    assert_eq!(sarif["version"], "2.1.0");
+    let results = sarif["runs"][0]["results"].as_array().unwrap();
+    assert_eq!(results.len(), 5);
+    assert_eq!(results[0]["ruleId"], "weak-digest");
+    let location = &results[0]["locations"][0];
+    assert_eq!(
+        location["physicalLocation"]["artifactLocation"]["uri"],
+        "data/testkey1.key"
+    );
+    assert_eq!(
+        location["logicalLocations"][0]["name"],
+        "73074D07F749A95CA6CFC95A4A3F7D2821923C80"
+    );
+    assert_eq!(audit::uri("keys/my key.asc"), "keys/my%20key.asc");
+    assert_eq!(audit::uri("/tmp/100%.asc"), "file:///tmp/100%25.asc");
+    assert_eq!(
+        audit::uri(r"C:\my keys\key.asc"),
+        "file:///C:/my%20keys/key.asc"
+    );
+    assert_eq!(audit::uri(r"keys\key.asc"), "keys/key.asc");
+    assert_eq!(results[4]["ruleId"], "unprotected");
+    let rules = &sarif["runs"][0]["tool"]["driver"]["rules"];
+    assert_eq!(
+        rules[results[4]["ruleIndex"].as_u64().unwrap() as usize]["id"],
+        "unprotected"
+    );
+
+    let mut junit = Vec::new();
+    audit::write_junit(&reports, &mut junit).unwrap();
+    let junit = String::from_utf8(junit).unwrap();
+    assert!(junit.contains("<testsuites name=\"pgp2hc audit\" tests=\"5\" failures=\"5\">"));
+    assert!(junit.contains(
+        "<testcase classname=\"data/testkey1.key\" \
+         name=\"73074D07F749A95CA6CFC95A4A3F7D2821923C80 small-rsa-key\">"
+    ));
+    assert!(junit.contains("<failure type=\"unprotected\""));
+    assert_eq!(junit.matches("<testsuite ").count(), 2);
+}
+
+#[test]
+#[cfg(feature = "cost")]
+fn test_cost() {
+    let record = match Extractor::new()
+        .extract_file("data/testkey1.key")
+        .unwrap()
+        .next()
+    {
+        Some(Extracted::Record(r)) => r,
+        _ => panic!("expected a record"),
+    };
+    // 65011712 bytes of SHA-1, 668 bytes of AES-128 and the SHA-1 hash of 648 bytes
+    let cost = Cost::of(&record.hash, 8).unwrap();
+    assert_eq!(
+        (cost.contexts, cost.hashed_bytes, cost.s2k_blocks),
+        (1, 65011712, 1015809)
+    );
+    assert_eq!((cost.cipher_blocks, cost.check_blocks), (42.0, 11.0));
+
+    // a second digest context for the longer key, preloaded with a zero byte
+    let mut hash = record.hash.clone();
+    hash.cipher_algorithm = hash::CipherAlgorithm::AES256;
+    let cost = Cost::of(&hash, 8).unwrap();
+    assert_eq!((cost.contexts, cost.s2k_blocks), (2, 2 * 1015809));
+    // the salt and passphrase are hashed at least once
+    hash.count = Some(1024);
+    assert_eq!(Cost::of(&hash, 2000).unwrap().hashed_bytes, 2008);
+    hash.s2k = hash::StringToKey::Simple;
+    hash.hash_algorithm = hash::HashAlgorithm::SHA512;
+    let cost = Cost::of(&hash, 200).unwrap();
+    assert_eq!((cost.contexts, cost.s2k_blocks), (1, 2));
+    hash.hash_algorithm = hash::HashAlgorithm::Unknown;
+    assert!(Cost::of(&hash, 8).is_err());
+
+    // the rest of a message is only decrypted and its MDC only hashed after the quick check, for
+    // one in 65536 candidates
+    let message = match Extractor::new()
+        .extract_file("data/symmetric1.gpg")
+        .unwrap()
+        .next()
+    {
+        Some(Extracted::Record(r)) => r.hash,
+        _ => panic!("expected a record"),
+    };
+    assert_eq!(message.usage, Usage::Eighteen);
+    let mdc_blocks = (message.data.len() - 20 + 9).div_ceil(64) as f64;
+    let cost = Cost::of(&message, 8).unwrap();
+    assert_eq!(cost.check_blocks, mdc_blocks / 65536.0);
+    let data_blocks = message.data.len().div_ceil(16) as f64;
+    assert_eq!(cost.cipher_blocks, 2.0 + data_blocks / 65536.0);
+
+    let calibration = Calibration::from_toml(
+        "threads = 4\nscaling = 3.5\n[digests]\nsha1 = 1015809.0\n[ciphers]\naes128 = 1e9\n",
+    )
+    .unwrap();
+    assert_eq!(
+        Calibration::from_toml(&calibration.to_toml()).unwrap(),
+        calibration
+    );
+    let cost = Cost::of(&record.hash, 8).unwrap();
+    let speed = calibration.guesses_per_second(&cost).unwrap();
+    assert!((speed - 1.0).abs() < 0.001);
+    assert!((calibration.guesses_per_second_all(&cost).unwrap() - 3.5).abs() < 0.01);
+    let mut cost = cost;
+    cost.cipher = hash::CipherAlgorithm::CAST5;
+    assert_eq!(calibration.guesses_per_second(&cost), None);
+    assert!(matches!(
+        Calibration::from_toml("threads = \"four\""),
+        Err(Error::InvalidCalibration(_))
+    ));
+}
+
+#[test]
+#[cfg(feature = "cost")]
+fn test_benchmark() {
+    use hash::Named;
+
+    let calibration = benchmark::Benchmark::new()
+        .digests(&[hash::HashAlgorithm::SHA1, hash::HashAlgorithm::MD5])
+        .ciphers(&[hash::CipherAlgorithm::AES128])
+        .counts(&[1024, 65536])
+        .threads(2)
+        .duration(std::time::Duration::from_millis(1))
+        .reference(true)
+        .run(|m| assert!(m.single_core > 0.0 && m.all_cores > 0.0 && m.reference.unwrap() > 0.0))
+        .unwrap();
+    assert_eq!(calibration.measurements.len(), 4);
+    assert_eq!(
+        calibration.digests.keys().collect::<Vec<_>>(),
+        ["md5", "sha1"]
+    );
+    assert!(
+        calibration
+            .cipher_speed(hash::CipherAlgorithm::AES128)
+            .unwrap()
+            > 0.0
+    );
+    assert_eq!(calibration.threads, 2);
+
+    let path = std::env::temp_dir().join(format!("pgp2hc-{}/calibration.toml", std::process::id()));
+    calibration.save(&path).unwrap();
+    assert_eq!(
+        Calibration::find(Some(&path)).unwrap(),
+        Some(calibration.clone())
+    );
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+    // the calibration gives the measured speed back at the highest count
+    for m in calibration.measurements.iter().filter(|m| m.count == 65536) {
+        let digest = hash::HashAlgorithm::from_name(&m.digest).unwrap();
+        let hash = benchmark::sample_hash(digest, hash::CipherAlgorithm::AES128, m.count);
+        let cost = Cost::of(&hash, 8).unwrap();
+        assert_eq!(cost.check_blocks, 0.0);
+        let speed = calibration.guesses_per_second(&cost).unwrap();
+        assert!(
+            (speed - m.single_core).abs() < m.single_core * 1e-9,
+            "{m:?}"
+        );
+    }
+
+    // the hashes are tested one after the other, the threads share the work
+    let record = match Extractor::new()
+        .extract_file("data/testkey1.key")
+        .unwrap()
+        .next()
+    {
+        Some(Extracted::Record(r)) => r,
+        _ => panic!("expected a record"),
+    };
+    let cost = Cost::of(&record.hash, 8).unwrap();
+    let single = calibration.guesses_per_second(&cost).unwrap();
+    let both = calibration
+        .candidates_per_second(&[cost.clone(), cost], 1)
+        .unwrap();
+    assert!((both - single / 2.0).abs() < single * 1e-9);
+}
+
+#[test]
+fn test_s2k() {
+    use hash::{CipherAlgorithm as C, HashAlgorithm as H, Named};
+    use s2k::{key_derivation, reference_derivation};
+
+    let mut hash = PgpHash {
+        algorithm: Algorithm::Symmetric,
+        data_len: 0,
+        bits: None,
+        data: Vec::new(),
+        s2k: hash::StringToKey::IteratedSalted,
+        usage: Usage::Eighteen,
+        hash_algorithm: H::SHA1,
+        cipher_algorithm: C::AES256,
+        iv_len: None,
+        iv: None,
+        count: Some(65536),
+        salt: Some(*b"pgp2hc\x00\x01"),
+        extra_data: None,
+    };
+    // computed with Python's hashlib
+    let known = [
+        (
+            H::SHA1,
+            C::AES256,
+            "password",
+            65536,
+            "2eb128d63e98288295a9c51e20dfc6c13b05ee682b12f7a20ca8e47fe78c832a",
+        ),
+        (
+            H::SHA512,
+            C::AES128,
+            "hunter2",
+            1025,
+            "107efc43302d3db0a16436eef9b57e9f",
+        ),
+        (
+            H::MD5,
+            C::AES256,
+            "correct horse battery staple",
+            262144,
+            "c48af3cfbbc8a3a92b65849a0a30fb87aa790d47b0832a7bc1ee7ab9c8bdc811",
+        ),
+        (
+            H::SHA256,
+            C::AES192,
+            "pässwörd",
+            1048576,
+            "b82c276e529024cf7fab6d3fdc7e704708e3cdceb39ddcc6",
+        ),
+        (
+            H::SHA224,
+            C::AES256,
+            &"x".repeat(120),
+            4096,
+            "ab3f57a63b0238e252ff880d0fcd68cad66c06fd7a7369745020f82ba15d0277",
+        ),
+        (
+            H::SHA384,
+            C::AES256,
+            "",
+            1024,
+            "fec382da7b72767e9f9eeef9350074e409ffbb4d382919a0c4922afb117dfe16",
+        ),
+        (
+            H::RIPEMD160,
+            C::AES256,
+            "password",
+            65536,
+            "4aa7d553826072e852ce414e74f936d89b8441e25943c0802e4d1de85ea2065b",
+        ),
+    ];
+    for (digest, cipher, passphrase, count, key) in known {
+        hash.hash_algorithm = digest;
+        hash.cipher_algorithm = cipher;
+        hash.count = Some(count);
+        let fast = key_derivation(&hash).unwrap().derive(passphrase.as_bytes());
+        let reference = reference_derivation(&hash)
+            .unwrap()
+            .derive(passphrase.as_bytes());
+        assert_eq!(hex::encode(fast), key, "{digest:?} {count}");
+        assert_eq!(hex::encode(reference), key, "{digest:?} {count}");
+    }
+
+    // every offset of the passphrase in the blocks, one derivation reused for all lengths
+    let passphrases: Vec<Vec<u8>> = (0..150).map(|n| (0..n as u8).collect()).collect();
+    for digest in H::all().into_iter().filter(|d| *d != H::Unknown) {
+        for cipher in [C::CAST5, C::AES192, C::AES256] {
+            for count in [64, 1024, 1031, 4096] {
+                hash.hash_algorithm = digest;
+                hash.cipher_algorithm = cipher;
+                hash.count = Some(count);
+                let mut fast = key_derivation(&hash).unwrap();
+                let mut reference = reference_derivation(&hash).unwrap();
+                for passphrase in passphrases.iter().chain(passphrases.iter().rev()) {
+                    assert_eq!(
+                        fast.derive(passphrase),
+                        reference.derive(passphrase),
+                        "{digest:?} {cipher:?} {count} {}",
+                        passphrase.len()
+                    );
+                }
+            }
+        }
+    }
+
+    // against the pgp crate
+    let mut rng = rand::thread_rng();
+    for (digest, pgp_digest) in [
+        (H::SHA1, HashAlgorithm::SHA1),
+        (H::SHA256, HashAlgorithm::SHA2_256),
+        (H::SHA512, HashAlgorithm::SHA2_512),
+    ] {
+        let s2k = StringToKey::new_iterated(&mut rng, pgp_digest, 96);
+        hash.hash_algorithm = digest;
+        hash.cipher_algorithm = C::AES256;
+        hash.count = s2k.count();
+        hash.salt = Some(s2k.salt().unwrap().try_into().unwrap());
+        assert_eq!(
+            verify::derive_key(&hash, b"test").unwrap(),
+            s2k.derive_key("test", 32).unwrap()
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "describe")]
+fn test_describe() {
+    use describe::{crackers, describe, Cracker};
+
+    let record = match Extractor::new()
+        .extract_file("data/testkey1.key")
+        .unwrap()
+        .next()
+    {
+        Some(Extracted::Record(r)) => r,
+        _ => panic!("expected a record"),
+    };
+    let mut hash = record.hash;
+    let fields = describe(&hash, 8);
+    let names: Vec<_> = fields.iter().map(|f| f.name).collect();
+    assert_eq!(
+        names,
+        [
+            "algorithm",
+            "data_len",
+            "bits",
+            "data",
+            "s2k",
+            "usage",
+            "hash_algorithm",
+            "cipher_algorithm",
+            "iv_len",
+            "iv",
+            "count",
+            "salt",
+            "extra_data"
+        ]
+    );
+    // the indices match the fields of the hash line
+    let line = hash.to_string();
+    let tokens: Vec<_> = line["$gpg$*".len()..].split('*').collect();
+    for field in &fields[..fields.len() - 1] {
+        let token = tokens[field.index.unwrap() - 1];
+        assert!(token.starts_with(field.value.trim_end_matches("...")));
+    }
+    assert_eq!(fields[10].value, "65011712");
+    assert!(fields[10].meaning.contains("coded 255"));
+    assert!(fields[10]
+        .meaning
+        .contains("8-byte passphrase 4063232 times"));
+    // the salt and the passphrase are hashed at least once
+    hash.count = Some(1000);
+    let fields = describe(&hash, 2000);
+    assert!(fields[10].meaning.contains("2000-byte passphrase 1 times"));
+    assert!(fields[10].meaning.contains("not a coded count"));
+    hash.count = Some(65011712);
+    assert_eq!(fields[12].index, None);
+    assert_eq!(
+        crackers(&hash),
+        [
+            Cracker::Pgp2hc,
+            Cracker::John("gpg"),
+            Cracker::John("gpg-opencl"),
+            Cracker::Hashcat(17010)
+        ]
+    );
+
+    // hashcat needs the SHA-1 check, john's OpenCL format an iterated string-to-key function
+    hash.usage = Usage::TwoFiveFive;
+    hash.s2k = hash::StringToKey::Salted;
+    hash.extra_data = Some(hash::ExtraData::Rsa { p: vec![0xff; 256] });
+    let fields = describe(&hash, 8);
+    let extra = fields.last().unwrap();
+    assert_eq!((extra.index, extra.value.as_str()), (Some(13), "RSA"));
+    assert!(extra.meaning.contains("n (256 bytes)"));
+    assert_eq!(crackers(&hash), [Cracker::Pgp2hc, Cracker::John("gpg")]);
+    hash.cipher_algorithm = hash::CipherAlgorithm::Unknown;
+    assert_eq!(crackers(&hash), []);
+    assert_eq!(Cracker::Hashcat(17010).to_string(), "hashcat -m 17010");
+}
+
+#[test]
+fn test_check() {
+    let line = std::fs::read_to_string("data/testkey1.hash").unwrap();
+    let hash = line.split(':').nth(1).unwrap();
+    assert!(check_hash(hash).is_ok());
+
+    let error = |hash: &str| {
+        let e = check_hash(hash).unwrap_err();
+        (e.index, e.name)
+    };
+    assert_eq!(error("gpg*1*668"), (0, "prefix"));
+    assert_eq!(error("$gpg$*1*668"), (3, "bits"));
+    assert_eq!(error("$gpg$*1*abc*2048"), (2, "data_len"));
+    assert_eq!(error(&hash.replacen("*1*", "*7*", 1)), (1, "algorithm"));
+    assert_eq!(error(&hash[..hash.len() - 1]), (12, "salt"));
+    assert_eq!(error(&format!("{hash}*00")), (13, "end"));
+    assert_eq!(error(&format!("{hash}*")), (13, "end"));
+    assert_eq!(error(&hash.replace("*254*", "*9*")), (6, "usage"));
+    assert_eq!(error(&hash.replace("*668*", "*667*")), (4, "data"));
+    let e = check_hash(&hash.replace("*16*", "*8*")).unwrap_err();
+    assert_eq!((e.index, e.name), (10, "iv"));
+    assert_eq!(
+        e.to_string(),
+        "field 10 (iv): 16 bytes long, but iv_len is 8"
+    );
+    // the extra data of usage 255
+    let short = hash.replace("*254*", "*255*") + "*3*0102";
+    assert_eq!(error(&short), (14, "p"));
+    assert!(
+        matches!(parse_hash(&short), Err(Error::HashParse(m)) if m.starts_with("field 14 (p)"))
+    );
+
+    #[cfg(feature = "cli")]
+    {
+        let path = env::temp_dir().join(format!("pgp2hc-check-{}.txt", std::process::id()));
+        let lines = [&line, "", hash, &hash[..100], "no hash", &short];
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        let args = cli::CheckArgs {
+            paths: vec![path.clone()],
+        };
+        assert_eq!(cli::check(&args).unwrap(), 3);
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[cfg(feature = "cli")]
 fn strip_first_last(h: &str) -> String {
     let data: Vec<_> = h.split(":").collect();
     data[0..data.len() - 1]
@@ -104,6 +1769,7 @@ fn strip_first_last(h: &str) -> String {
 }
 
 // given multiple lines with hashes, check that they are the same
+#[cfg(feature = "cli")]
 fn hash_output_eq(hash1: &str, hash2: &str) {
     let mut it1 = hash1.lines();
     let mut it2 = hash2.lines();
@@ -115,6 +1781,7 @@ fn hash_output_eq(hash1: &str, hash2: &str) {
 
 /// test utf-8 sample in the john-samples repository
 #[test]
+#[cfg(feature = "cli")]
 fn test_john_utf8_samples() {
     dotenv::dotenv().unwrap();
     // require an environment variable specifying the path to the john-samples repository root
@@ -132,10 +1799,14 @@ fn test_john_utf8_samples() {
     let mut fname = path.clone();
     fname.push("test.utf8.asc");
     let hash = extract_hash(&Cli {
-        path: fname.clone(),
+        command: None,
+        paths: vec![fname.clone()],
         format: HashFormat::John,
         _no_dearmor: false,
         _no_subkeys: true,
+        dedup: false,
+        potfile: None,
+        left: false,
     })
     .unwrap();
     let hash_john = Command::new(johnpath.to_str().unwrap())
@@ -149,6 +1820,7 @@ fn test_john_utf8_samples() {
 
 /// test all GPG samples in the john-samples repository
 #[test]
+#[cfg(feature = "cli")]
 fn test_john_samples() {
     let _ = dotenv::dotenv();
     // require an environment variable specifying the path to the john-samples repository root
@@ -177,10 +1849,14 @@ fn test_john_samples() {
                 let hash_john = std::str::from_utf8(&hash_john).unwrap();
                 // get our hash
                 let h_me = extract_hash(&Cli {
-                    path: p,
+                    command: None,
+                    paths: vec![p],
                     format: HashFormat::John,
                     _no_dearmor: false,
                     _no_subkeys: true,
+                    dedup: false,
+                    potfile: None,
+                    left: false,
                 })
                 .unwrap();
                 // first and last parts are different
@@ -0,0 +1,338 @@
+use crate::convert::MAX_SYMMETRIC_DATA_LEN;
+#[cfg(feature = "encoding")]
+use crate::encoding::{encode_variants, Encoding};
+use crate::error::Error;
+use crate::hash::*;
+use crate::s2k::{key_derivation, KeyDerivation};
+use cipher::{generic_array::GenericArray, BlockEncrypt, KeyInit};
+use digest::Digest;
+use rsa::BigUint;
+
+impl PgpHash {
+    /// Check whether `passphrase` unlocks the key or message this hash was extracted from.
+    ///
+    /// The key is derived with the hash's string-to-key specifier and the encrypted data is
+    /// decrypted in the CFB variant given by `usage`. For keys, the SHA-1 hash (usage 254) or the
+    /// 16-bit checksum (usage 0 and 255) of the secret key material is checked; if `extra_data`
+    /// is present, the decrypted key is also checked against the public key, which rules out
+    /// checksum collisions. For symmetric hashes, the quick check bytes and the MDC (usage 18)
+    /// are checked. Without an MDC (usage 9) or if the data was cut to its maximum length, the
+    /// first decrypted packet is checked for plausibility instead, which can in rare cases accept
+    /// a wrong passphrase.
+    ///
+    /// Returns an error if the hash uses an unknown algorithm or misses required fields.
+    pub fn verify(&self, passphrase: &str) -> Result<bool, Error> {
+        self.verify_bytes(passphrase.as_bytes())
+    }
+
+    /// Like `verify`, trying the passphrase in each of `encodings`.
+    /// Returns the first encoding that matched, if any.
+    #[cfg(feature = "encoding")]
+    pub fn verify_encodings(
+        &self,
+        passphrase: &str,
+        encodings: &[Encoding],
+    ) -> Result<Option<Encoding>, Error> {
+        for (encoding, bytes) in encode_variants(passphrase.as_bytes(), encodings) {
+            if self.verify_bytes(&bytes)? {
+                return Ok(Some(encoding));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Like `verify`, for passphrases that are not valid UTF-8
+    pub fn verify_bytes(&self, passphrase: &[u8]) -> Result<bool, Error> {
+        self.verifier()?.verify_bytes(passphrase)
+    }
+
+    /// A verifier for testing many candidates against this hash. Returns an error if the hash
+    /// uses an unknown algorithm or misses the salt or count.
+    pub(crate) fn verifier(&self) -> Result<Verifier<'_>, Error> {
+        Ok(Verifier {
+            hash: self,
+            s2k: key_derivation(self)?,
+            plaintext: Vec::new(),
+        })
+    }
+
+    /// Check the key derived from a candidate by decrypting the encrypted data into `plaintext`.
+    /// Symmetric data is decrypted completely only if its quick check bytes match.
+    fn check_key(&self, key: &[u8], plaintext: &mut Vec<u8>) -> Result<bool, Error> {
+        match self.algorithm {
+            Algorithm::Symmetric => {
+                let resync = match self.usage {
+                    Usage::Nine => true,
+                    Usage::Eighteen => false,
+                    u => {
+                        return Err(Error::UnsupportedS2k(format!(
+                            "usage {} for symmetric data",
+                            u as i32
+                        )))
+                    }
+                };
+                let bs = self.cipher_algorithm.block_size().unwrap();
+                if self.data.len() < bs + 2 {
+                    return Err(Error::HashParse("encrypted data is too short".into()));
+                }
+                let iv = [0; 16];
+                // the random prefix and the quick check bytes come before any resynchronisation
+                let mut prefix = [0; 18];
+                let prefix = &mut prefix[..bs + 2];
+                prefix.copy_from_slice(&self.data[..bs + 2]);
+                decrypt(self.cipher_algorithm, key, &iv[..bs], prefix, false)?;
+                if prefix[bs - 2..bs] != prefix[bs..] {
+                    return Ok(false);
+                }
+                plaintext.clear();
+                plaintext.extend_from_slice(&self.data);
+                decrypt(self.cipher_algorithm, key, &iv[..bs], plaintext, resync)?;
+                let complete = self.data_len != MAX_SYMMETRIC_DATA_LEN;
+                Ok(check_symmetric(plaintext, bs, self.usage, complete))
+            }
+            _ => {
+                let iv = self
+                    .iv
+                    .as_ref()
+                    .ok_or_else(|| Error::HashParse("missing IV".into()))?;
+                plaintext.clear();
+                plaintext.extend_from_slice(&self.data);
+                decrypt(self.cipher_algorithm, key, iv, plaintext, false)?;
+                Ok(check_secret_key(self, plaintext))
+            }
+        }
+    }
+}
+
+/// Tests candidate passphrases against one hash like [`PgpHash::verify_bytes`], keeping the
+/// buffers of the string-to-key function and the decrypted data between candidates
+pub(crate) struct Verifier<'a> {
+    hash: &'a PgpHash,
+    s2k: Box<dyn KeyDerivation>,
+    plaintext: Vec<u8>,
+}
+
+impl<'a> Verifier<'a> {
+    /// A verifier with another key derivation than the fastest one, e.g. the reference
+    /// implementation
+    #[cfg(feature = "cost")]
+    pub(crate) fn with_derivation(hash: &'a PgpHash, s2k: Box<dyn KeyDerivation>) -> Self {
+        Verifier {
+            hash,
+            s2k,
+            plaintext: Vec::new(),
+        }
+    }
+
+    pub(crate) fn verify_bytes(&mut self, passphrase: &[u8]) -> Result<bool, Error> {
+        let key = self.s2k.derive(passphrase);
+        self.hash.check_key(&key, &mut self.plaintext)
+    }
+}
+
+/// Derive the key from the passphrase with the hash's string-to-key specifier
+#[cfg(any(feature = "decrypt", test))]
+pub(crate) fn derive_key(hash: &PgpHash, passphrase: &[u8]) -> Result<Vec<u8>, Error> {
+    Ok(key_derivation(hash)?.derive(passphrase))
+}
+
+/// Decrypt `data` in place in CFB mode. With `resync`, the OpenPGP CFB variant of RFC4880,
+/// Section 13.9 is used, which restarts the cipher after the random prefix.
+pub(crate) fn decrypt(
+    alg: CipherAlgorithm,
+    key: &[u8],
+    iv: &[u8],
+    data: &mut [u8],
+    resync: bool,
+) -> Result<(), Error> {
+    match alg {
+        CipherAlgorithm::Unknown => Err(Error::UnsupportedAlgorithm("unknown cipher".into())),
+        CipherAlgorithm::IDEA => decrypt_with::<idea::Idea>(key, iv, data, resync),
+        CipherAlgorithm::TripleDES => decrypt_with::<des::TdesEde3>(key, iv, data, resync),
+        CipherAlgorithm::CAST5 => decrypt_with::<cast5::Cast5>(key, iv, data, resync),
+        CipherAlgorithm::Blowfish => decrypt_with::<blowfish::Blowfish>(key, iv, data, resync),
+        CipherAlgorithm::AES128 => decrypt_with::<aes::Aes128>(key, iv, data, resync),
+        CipherAlgorithm::AES192 => decrypt_with::<aes::Aes192>(key, iv, data, resync),
+        CipherAlgorithm::AES256 => decrypt_with::<aes::Aes256>(key, iv, data, resync),
+        CipherAlgorithm::Twofish => decrypt_with::<twofish::Twofish>(key, iv, data, resync),
+        CipherAlgorithm::Camellia128 => {
+            decrypt_with::<camellia::Camellia128>(key, iv, data, resync)
+        }
+        CipherAlgorithm::Camellia192 => {
+            decrypt_with::<camellia::Camellia192>(key, iv, data, resync)
+        }
+        CipherAlgorithm::Camellia256 => {
+            decrypt_with::<camellia::Camellia256>(key, iv, data, resync)
+        }
+    }
+}
+
+fn decrypt_with<C: KeyInit + BlockEncrypt>(
+    key: &[u8],
+    iv: &[u8],
+    data: &mut [u8],
+    resync: bool,
+) -> Result<(), Error> {
+    let cipher =
+        C::new_from_slice(key).map_err(|_| Error::HashParse("invalid key length".into()))?;
+    let bs = C::block_size();
+    if iv.len() != bs {
+        return Err(Error::HashParse(
+            "IV length doesn't match the cipher".into(),
+        ));
+    }
+    if !resync || data.len() <= bs + 2 {
+        cfb_decrypt(&cipher, iv, data);
+        return Ok(());
+    }
+    let (prefix, rest) = data.split_at_mut(bs + 2);
+    let next_iv = prefix[2..].to_vec();
+    cfb_decrypt(&cipher, iv, prefix);
+    cfb_decrypt(&cipher, &next_iv, rest);
+    Ok(())
+}
+
+fn cfb_decrypt<C: BlockEncrypt>(cipher: &C, iv: &[u8], data: &mut [u8]) {
+    let mut register = GenericArray::clone_from_slice(iv);
+    for chunk in data.chunks_mut(iv.len()) {
+        let mut keystream = register.clone();
+        cipher.encrypt_block(&mut keystream);
+        register[..chunk.len()].copy_from_slice(chunk);
+        for (b, k) in chunk.iter_mut().zip(keystream.iter()) {
+            *b ^= k;
+        }
+    }
+}
+
+/// Check decrypted symmetric data: random prefix with quick check bytes, followed by packets.
+/// `complete` is false if the data was cut off, which may have removed the MDC.
+fn check_symmetric(plaintext: &[u8], bs: usize, usage: Usage, complete: bool) -> bool {
+    if plaintext[bs - 2..bs] != plaintext[bs..bs + 2] {
+        return false;
+    }
+    // complete integrity protected data ends with a modification detection code packet
+    if usage == Usage::Eighteen && complete {
+        let Some(split) = plaintext.len().checked_sub(20).filter(|&i| i >= bs + 4) else {
+            return false;
+        };
+        let (content, mdc) = plaintext.split_at(split);
+        return content.ends_with(&[0xd3, 0x14]) && sha1::Sha1::digest(content)[..] == *mdc;
+    }
+    plausible_packet(&plaintext[bs + 2..])
+}
+
+/// Whether `data` starts with a packet that can appear at the start of a decrypted message
+fn plausible_packet(data: &[u8]) -> bool {
+    let Some(&header) = data.first() else {
+        return false;
+    };
+    if header & 0x80 == 0 {
+        return false;
+    }
+    let (tag, body) = if header & 0x40 != 0 {
+        // new format: one, two or five length bytes, or a partial length
+        let tag = header & 0x3f;
+        let header_len = match data.get(1) {
+            Some(0..=191) | Some(224..=254) => 2,
+            Some(192..=223) => 3,
+            Some(255) => 6,
+            None => return false,
+        };
+        (tag, data.get(header_len..))
+    } else {
+        let tag = (header >> 2) & 0x0f;
+        let header_len = match header & 0x03 {
+            0 => 2,
+            1 => 3,
+            2 => 5,
+            _ => 1,
+        };
+        (tag, data.get(header_len..))
+    };
+    let Some(body) = body else {
+        return false;
+    };
+    match (tag, body) {
+        // compressed data: uncompressed, ZIP, ZLIB or BZip2
+        (8, [0 | 1 | 3, ..]) => true,
+        (8, [2, cmf, flg, ..]) => {
+            cmf & 0x0f == 8 && (u16::from(*cmf) * 256 + u16::from(*flg)) % 31 == 0
+        }
+        // literal data: format and file name length
+        (11, [format, name_len, ..]) => {
+            b"btul1m".contains(format) && body.len() >= 2 + usize::from(*name_len)
+        }
+        // signature and one-pass signature
+        (2, [3 | 4, ..]) | (4, [3, ..]) => true,
+        _ => false,
+    }
+}
+
+/// Check decrypted secret key material: MPIs followed by a SHA-1 hash or 16-bit checksum
+fn check_secret_key(hash: &PgpHash, plaintext: &[u8]) -> bool {
+    let mpis = match hash.usage {
+        Usage::TwoFiveFour => {
+            if plaintext.len() < 20 {
+                return false;
+            }
+            let (mpis, sha) = plaintext.split_at(plaintext.len() - 20);
+            return sha1::Sha1::digest(mpis)[..] == *sha;
+        }
+        _ => {
+            if plaintext.len() < 2 {
+                return false;
+            }
+            let (mpis, checksum) = plaintext.split_at(plaintext.len() - 2);
+            let sum = mpis.iter().fold(0u16, |s, &b| s.wrapping_add(b.into()));
+            if sum.to_be_bytes() != checksum {
+                return false;
+            }
+            mpis
+        }
+    };
+    let Some(mpis) = parse_mpis(mpis) else {
+        return false;
+    };
+    let expected = match hash.algorithm {
+        Algorithm::RSAEncSign | Algorithm::RSAEncOnly | Algorithm::RsaSignOnly => Some(4),
+        Algorithm::ElGamal
+        | Algorithm::DSA
+        | Algorithm::EC
+        | Algorithm::ECDSA
+        | Algorithm::ElGamalEncSign => Some(1),
+        _ => None,
+    };
+    if expected.is_some_and(|n| n != mpis.len()) {
+        return false;
+    }
+    let int = BigUint::from_bytes_be;
+    match (&hash.extra_data, mpis.as_slice()) {
+        (None, _) => true,
+        // d, p, q, u
+        (Some(ExtraData::Rsa { p: n }), [_, p, q, _]) => int(p) * int(q) == int(n),
+        (Some(ExtraData::Dsa { p, q, g, y }), [x]) => {
+            int(x) < int(q) && int(g).modpow(&int(x), &int(p)) == int(y)
+        }
+        (Some(ExtraData::ElGamal { p, g, y }), [x]) => int(g).modpow(&int(x), &int(p)) == int(y),
+        _ => false,
+    }
+}
+
+/// Split multiprecision integers (RFC4880, Section 3.2), None if they are malformed
+fn parse_mpis(mut data: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut mpis = Vec::new();
+    while !data.is_empty() {
+        let bits = usize::from(u16::from_be_bytes([*data.first()?, *data.get(1)?]));
+        let len = bits.div_ceil(8);
+        let mpi = data.get(2..2 + len)?;
+        // the bit count must match the leading byte
+        if let Some(&first) = mpi.first() {
+            if 8 * len - first.leading_zeros() as usize != bits {
+                return None;
+            }
+        }
+        mpis.push(mpi);
+        data = &data[2 + len..];
+    }
+    Some(mpis)
+}